@@ -1,3 +1,15 @@
+use std::fs::read_to_string;
+
 fn main() {
     println!("cargo:rustc-link-arg=-rdynamic");
+
+    // grug-sys doesn't expose its version, so it's read from the exact version it's pinned to
+    println!("cargo:rerun-if-changed=Cargo.toml");
+    let manifest = read_to_string("Cargo.toml").unwrap();
+    let grug_sys_version = manifest
+        .lines()
+        .find_map(|x| x.strip_prefix("grug-sys = \"="))
+        .and_then(|x| x.strip_suffix('"'))
+        .expect("grug-sys has to be pinned to an exact version, like `grug-sys = \"=0.1.5\"`");
+    println!("cargo:rustc-env=GRUG_SYS_VERSION={grug_sys_version}");
 }
@@ -0,0 +1,41 @@
+use grug_rs::{Arguments, Grug, table::TableRow};
+
+use anyhow::Result;
+use grug_rs_proc_macro::{game_function, grug_table};
+
+fn main() -> Result<()> {
+    // Initializes grug
+    let grug = Grug::new(
+        None,
+        "./examples/data_table/mod_api.json",
+        "./examples/data_table/mods",
+        "./examples/data_table/mods_dll",
+        1000,
+    )?;
+
+    grug.publish_table(
+        "items",
+        [(
+            1,
+            TableRow::from([
+                ("name".to_string(), "Sword".into()),
+                ("damage".to_string(), 12.into()),
+            ]),
+        )],
+    );
+
+    grug.activate_on_function("World", "on_update", &mut Arguments::empty())?;
+    Ok(())
+}
+
+grug_table!(items);
+
+#[game_function]
+fn println(message: String) {
+    println!("{message}");
+}
+
+#[game_function]
+fn println_int(message: i32) {
+    println!("{message}");
+}
@@ -0,0 +1,78 @@
+use std::{
+    fs::write,
+    sync::atomic::{AtomicI32, Ordering},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use grug_rs::{
+    Arguments, Grug,
+    headless::{QuarantinePolicy, silent_runtime_error_handler},
+    server_report::ServerScriptReport,
+};
+
+use anyhow::Result;
+use grug_rs_proc_macro::game_function;
+
+const TICK: Duration = Duration::from_millis(50);
+/// How many ticks go by between writes of `status.json`
+const STATUS_INTERVAL: u64 = 20 * 10;
+
+static POINTS: AtomicI32 = AtomicI32::new(0);
+
+fn main() -> Result<()> {
+    // Runtime errors don't get printed, they show up in `status.json` instead
+    let grug = Grug::new(
+        Some(silent_runtime_error_handler),
+        "./examples/headless/mod_api.json",
+        "./examples/headless/mods",
+        "./examples/headless/mods_dll",
+        1000,
+    )?;
+
+    // Mods only get reloaded when the admin sends a SIGHUP, like `kill -HUP <pid>`
+    grug.set_auto_regenerate(false);
+    #[cfg(unix)]
+    grug.reload_on_sighup();
+
+    grug.set_quarantine_policy(Some(QuarantinePolicy {
+        max_runtime_errors: 100,
+    }));
+
+    let mut nondeterministic_calls = 0;
+    for tick in 0.. {
+        let start = Instant::now();
+
+        if grug.take_reload_request() {
+            // A mod that fails to compile leaves the old version running, and shows up in the next report
+            let _ = grug.regenerate_modified_mods();
+            grug.release_quarantined_files();
+        }
+
+        // Every client runs the same ticks, so scripts can't read anything that differs between them
+        grug.begin_deterministic_step();
+        grug.activate_on_function("World", "on_update", &mut Arguments::empty())?;
+        nondeterministic_calls += grug.end_deterministic_step().len();
+
+        if tick % STATUS_INTERVAL == 0 {
+            let report = ServerScriptReport::from(&grug.snapshot());
+            let status = serde_json::json!({
+                "tick": tick,
+                "points": POINTS.load(Ordering::Relaxed),
+                "scripts": report,
+                "quarantined_files": grug.quarantined_files().len(),
+                "nondeterministic_calls": nondeterministic_calls,
+            });
+            write("./examples/headless/status.json", status.to_string())?;
+        }
+
+        sleep(TICK.saturating_sub(start.elapsed()));
+    }
+
+    Ok(())
+}
+
+#[game_function]
+fn award_points(points: i32) {
+    POINTS.fetch_add(points, Ordering::Relaxed);
+}
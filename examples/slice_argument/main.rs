@@ -0,0 +1,23 @@
+use grug_rs::{Arguments, Grug};
+
+use anyhow::Result;
+use grug_rs_proc_macro::game_function;
+
+fn main() -> Result<()> {
+    // Initializes grug
+    let grug = Grug::new(
+        None,
+        "./examples/slice_argument/mod_api.json",
+        "./examples/slice_argument/mods",
+        "./examples/slice_argument/mods_dll",
+        1000,
+    )?;
+
+    grug.activate_on_function("World", "on_update", &mut Arguments::empty())?;
+    Ok(())
+}
+
+#[game_function]
+fn print_sum(label: String, numbers: &[i32]) {
+    println!("{label}: {}", numbers.iter().sum::<i32>());
+}
@@ -1,9 +1,10 @@
 use std::{collections::HashMap, mem::swap};
 
 use proc_macro::TokenStream;
-use quote::{ToTokens, quote};
+use quote::{ToTokens, format_ident, quote};
 use syn::{
-    Abi, Block, FnArg, Ident, ItemFn, Pat, Stmt, Type, TypePtr, parse_macro_input,
+    Abi, Block, Data, DeriveInput, Error, Expr, FnArg, Ident, ItemFn, Pat, ReturnType, Stmt, Type,
+    TypePtr, parse_macro_input, parse_quote,
     token::{Const, Star, Unsafe},
 };
 
@@ -57,25 +58,13 @@ pub fn error_handler(_attr: TokenStream, item: TokenStream) -> TokenStream {
 
     let conversion = format!(
         "{{// Convert inputs safely
-    let {0} = if !{0}.is_null() {{
-        unsafe {{ std::ffi::CStr::from_ptr({0}).to_string_lossy() }}
-    }} else {{
-        \"<unknown>\".into()
-    }}.to_string();
+    let {0} = unsafe {{ grug_rs::ffi_string::display_ptr({0}, \"<unknown>\") }};
 
     let {1} = unsafe {{ std::mem::transmute::<_, grug_rs::GrugRuntimeError>({1}) }};
 
-    let {2} = if !{2}.is_null() {{
-        unsafe {{ std::ffi::CStr::from_ptr({2}).to_string_lossy() }}
-    }} else {{
-        \"<unknown>\".into()
-    }}.to_string();
-
-    let {3} = if !{3}.is_null() {{
-        unsafe {{ std::ffi::CStr::from_ptr({3}).to_string_lossy() }}
-    }} else {{
-        \"<unknown>\".into()
-    }}.to_string();
+    let {2} = unsafe {{ grug_rs::ffi_string::display_ptr({2}, \"<unknown>\") }};
+
+    let {3} = unsafe {{ grug_rs::ffi_string::display_ptr({3}, \"<unknown>\") }};
 }}",
         names[0], names[1], names[2], names[3]
     )
@@ -121,13 +110,85 @@ fn grab_type_for_error_handler(idx: usize) -> TokenStream {
 ///     println!("{message}");
 /// }
 /// ```
+///
+/// Parameters can be `String`, references to custom types, or anything implementing `grug_rs::GrugParam`,
+/// which covers `i32`, `f32`, `bool` and `u64` ids.
+///
+/// The signature gets recorded too, so `Grug::check_game_functions` can compare it with `mod_api.json`.
+///
+/// A returned `String` gets copied into the frame arena, and stays valid until `Grug::end_frame`.
+///
+/// # Slices
+/// grug can't pass lists, so a function taking a slice gets split up into three game functions
+/// that share a staging buffer: `<name>_begin()` clears it, `<name>_push(item)` adds an item to it
+/// and `<name>_commit(...)` calls the function with the buffer and the rest of the arguments.
+/// All three have to be declared in `mod_api.json`.
+///
+/// Items can be `i32`, `f32`, `bool`, `String` or a custom type implementing `Clone`,
+/// which gets pushed as a reference and cloned into the buffer.
+/// ```
+/// #[game_function]
+/// fn set_path(points: &[Vec2]) {
+///     path.replace(points.to_vec());
+/// }
+/// ```
+/// ```grug
+/// set_path_begin()
+/// set_path_push(a)
+/// set_path_push(b)
+/// set_path_commit()
+/// ```
+///
+/// # Determinism
+/// Game functions that read wall clocks, OS randomness and the like should be tagged with
+/// `#[game_function(nondeterministic)]`, so calls to them during `Grug::begin_deterministic_step`
+/// get reported.
 #[proc_macro_attribute]
-pub fn game_function(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn game_function(attr: TokenStream, item: TokenStream) -> TokenStream {
     let mut input = parse_macro_input!(item as ItemFn);
 
+    if !attr.is_empty() {
+        let attr = parse_macro_input!(attr as Ident);
+        assert!(
+            attr == "nondeterministic",
+            "Unknown game_function option `{attr}`"
+        );
+
+        let name = input.sig.ident.to_string();
+        input.block.stmts.insert(
+            0,
+            parse_quote! { grug_rs::determinism::note_nondeterministic_call(#name); },
+        );
+    }
+
+    if let Some(index) = slice_argument(&input) {
+        return game_function_with_slice(input, index);
+    }
+
+    expand_game_function(input)
+}
+
+/// Turns `input` into a game function
+fn expand_game_function(mut input: ItemFn) -> TokenStream {
+    let signature = signature_of(&input);
+
+    // grug only gets a pointer, so returned strings go in the frame arena to outlive the call
+    if let ReturnType::Type(_, ty) = &input.sig.output
+        && ty.to_token_stream().to_string() == "String"
+    {
+        let block = input.block.clone();
+        *input.block = parse_quote! {{
+            #[allow(clippy::redundant_closure_call)]
+            let __grug_result: String = (|| -> String #block)();
+            grug_rs::frame::alloc_c_str(&__grug_result)
+        }};
+        input.sig.output = parse_quote! { -> *const std::ffi::c_char };
+    }
+
     let args = &mut input.sig.inputs;
 
     let mut types = HashMap::new();
+    let mut params = vec![];
 
     for arg in args {
         if let FnArg::Typed(pattern) = arg {
@@ -154,6 +215,14 @@ pub fn game_function(_attr: TokenStream, item: TokenStream) -> TokenStream {
                             &mut *pattern.ty,
                             &mut Type::Ptr(parse_macro_input!(c_string_type as TypePtr)),
                         );
+                    } else {
+                        // Everything else gets converted by its `GrugParam` impl
+                        params.push((*pattern.pat.clone(), type_path.clone()));
+
+                        if let Pat::Ident(ident) = &mut *pattern.pat {
+                            ident.mutability = None;
+                        }
+                        *pattern.ty = parse_quote! { <#type_path as grug_rs::GrugParam>::Raw };
                     }
                 }
                 Type::Reference(reference) => {
@@ -196,12 +265,14 @@ pub fn game_function(_attr: TokenStream, item: TokenStream) -> TokenStream {
         if type_ == "String" {
             // Only need to modify string types
             let to_string = format!(
-                "    let {0} = if !{0}.is_null() {{
-                unsafe {{ std::ffi::CStr::from_ptr({0}).to_string_lossy() }}
-            }} else {{
-                panic!(\"`{0}` is null.\")
-            }};
-        ",
+                "let {0} = match unsafe {{ grug_rs::ffi_string::str_from_argument({0}) }} {{
+                    Ok(x) => std::borrow::Cow::Borrowed(x),
+                    Err(error) => {{
+                        grug_rs::game_function_error(&format!(\"`{0}` {{error}}\"));
+                        // The script gets a runtime error, so the return value is never looked at
+                        return unsafe {{ std::mem::zeroed() }};
+                    }}
+                }};",
                 name
             )
             .parse()
@@ -227,6 +298,8 @@ pub fn game_function(_attr: TokenStream, item: TokenStream) -> TokenStream {
                 .block
                 .stmts
                 .insert(0, parse_macro_input!(to_reference as Stmt));
+
+            input.block.stmts.insert(0, borrow_custom(&name, false));
         } else if type_ == "PointerMut" {
             let to_reference = format!(
                 "let {0} = if !{0}.is_null() {{
@@ -243,9 +316,33 @@ pub fn game_function(_attr: TokenStream, item: TokenStream) -> TokenStream {
                 .block
                 .stmts
                 .insert(0, parse_macro_input!(to_reference as Stmt));
+
+            input.block.stmts.insert(0, borrow_custom(&name, true));
         }
     }
 
+    for (pattern, type_path) in params {
+        let Pat::Ident(ident) = &pattern else {
+            unreachable!()
+        };
+        let name = &ident.ident;
+
+        let name_string = name.to_string();
+        input.block.stmts.insert(
+            0,
+            parse_quote! {
+                let #pattern = match <#type_path as grug_rs::GrugParam>::validate(&#name) {
+                    Ok(()) => unsafe { <#type_path as grug_rs::GrugParam>::from_grug(#name) },
+                    Err(error) => {
+                        grug_rs::game_function_error(&format!("`{}` {error}", #name_string));
+                        // The script gets a runtime error, so the return value is never looked at
+                        return unsafe { std::mem::zeroed() };
+                    }
+                };
+            },
+        );
+    }
+
     // Need to add `unsafe extern "C"` to the function
     input.sig.unsafety = Some(Unsafe::default());
 
@@ -262,8 +359,380 @@ pub fn game_function(_attr: TokenStream, item: TokenStream) -> TokenStream {
 
     input.sig.ident = parse_macro_input!(ident as Ident);
 
-    TokenStream::from(quote! {
+    let mut output = TokenStream::from(quote! {
         #[unsafe(no_mangle)]
         #input
+    });
+    output.extend(signature);
+
+    output
+}
+
+/// Tracks the borrow of the custom value behind the reference argument `name`,
+/// returning from the game function if the script passed it to a conflicting argument
+fn borrow_custom(name: &str, mutable: bool) -> Stmt {
+    let name = format_ident!("{}", name);
+    let guard = format_ident!("__grug_borrow_{}", name);
+    let argument = name.to_string();
+
+    parse_quote! {
+        let #guard = match grug_rs::grug_value::borrow_custom(
+            #name as *mut std::ffi::c_void,
+            #mutable,
+            #argument,
+        ) {
+            Some(borrow) => borrow,
+            // The script gets a runtime error, so the return value is never looked at
+            None => return unsafe { std::mem::zeroed() },
+        };
+    }
+}
+
+/// The grug type a Rust type gets passed as, see `grug_rs::registry`
+fn grug_kind(ty: &Type) -> String {
+    match ty {
+        Type::Reference(_) => "id".to_string(),
+        Type::Ptr(pointer)
+            if pointer
+                .elem
+                .to_token_stream()
+                .to_string()
+                .ends_with("c_char") =>
+        {
+            "string".to_string()
+        }
+        Type::Ptr(_) => "id".to_string(),
+        _ => match ty.to_token_stream().to_string().as_str() {
+            "String" => "string".to_string(),
+            "u64" => "id".to_string(),
+            other => other.to_string(),
+        },
+    }
+}
+
+/// Same as `grug_kind`, but parameters going through `GrugParam` get their type from the impl
+fn argument_kind(ty: &Type) -> Expr {
+    match ty {
+        Type::Path(type_path) if type_path.to_token_stream().to_string() != "String" => {
+            parse_quote! { <#type_path as grug_rs::GrugParam>::GRUG_TYPE }
+        }
+        _ => literal(&grug_kind(ty)),
+    }
+}
+
+fn literal(kind: &str) -> Expr {
+    parse_quote! { #kind }
+}
+
+/// Records the signature of `input` for `Grug::check_game_functions`
+fn signature_of(input: &ItemFn) -> TokenStream {
+    let arguments: Vec<Expr> = input
+        .sig
+        .inputs
+        .iter()
+        .map(|arg| match arg {
+            FnArg::Typed(pattern) => argument_kind(&pattern.ty),
+            FnArg::Receiver(_) => panic!("Game functions can't take `self`"),
+        })
+        .collect();
+
+    let return_type = match &input.sig.output {
+        ReturnType::Default => "void".to_string(),
+        ReturnType::Type(_, ty) => grug_kind(ty),
+    };
+
+    register_signature(&input.sig.ident.to_string(), &arguments, &return_type)
+}
+
+fn register_signature(name: &str, arguments: &[Expr], return_type: &str) -> TokenStream {
+    TokenStream::from(quote! {
+        const _: () = {
+            #[used]
+            #[unsafe(link_section = "grug_rs_game_functions")]
+            static SIGNATURE: grug_rs::registry::GameFunctionSignature =
+                grug_rs::registry::GameFunctionSignature {
+                    name: #name,
+                    arguments: &[#(#arguments),*],
+                    return_type: #return_type,
+                };
+        };
+    })
+}
+
+/// Finds the index of the argument that takes a slice, if there is one
+fn slice_argument(input: &ItemFn) -> Option<usize> {
+    let slices: Vec<usize> = input
+        .sig
+        .inputs
+        .iter()
+        .enumerate()
+        .filter(|(_, arg)| slice_element(arg).is_some())
+        .map(|(i, _)| i)
+        .collect();
+
+    assert!(
+        slices.len() <= 1,
+        "Game functions can only take a single slice"
+    );
+
+    slices.first().copied()
+}
+
+/// Grabs the element type of an argument like `points: &[Vec2]`
+fn slice_element(arg: &FnArg) -> Option<Type> {
+    if let FnArg::Typed(pattern) = arg
+        && let Type::Reference(reference) = &*pattern.ty
+        && let Type::Slice(slice) = &*reference.elem
+    {
+        return Some(*slice.elem.clone());
+    }
+
+    None
+}
+
+/// Splits a function taking a slice into `<name>_begin`, `<name>_push` and `<name>_commit`
+fn game_function_with_slice(input: ItemFn, index: usize) -> TokenStream {
+    let name = input.sig.ident.clone();
+    let element = slice_element(&input.sig.inputs[index]).unwrap();
+
+    let name_string = name.to_string();
+    let staging = format_ident!("__GRUG_STAGING_{}", name.to_string().to_uppercase());
+    let begin = format_ident!("game_fn_{}_begin", name);
+    let push = format_ident!("game_fn_{}_push", name);
+
+    // How a single item comes in from grug, and how to turn it into an element
+    let element_name = element.to_token_stream().to_string();
+    let item_kind = match element_name.as_str() {
+        "i32" | "f32" | "bool" => literal(&element_name),
+        "String" => literal("string"),
+        _ => literal("id"),
+    };
+    let (item_type, to_element) = match element_name.as_str() {
+        "i32" | "f32" | "bool" => (quote! { #element }, quote! { item }),
+        "String" => (
+            quote! { *const std::ffi::c_char },
+            quote! {
+                match unsafe { grug_rs::ffi_string::str_from_ptr(item) } {
+                    Ok(x) => x.to_string(),
+                    Err(error) => return grug_rs::game_function_error(&format!("`item` {error}")),
+                }
+            },
+        ),
+        _ => (
+            quote! { *const #element },
+            quote! {
+                if !item.is_null() {
+                    unsafe { (*item).clone() }
+                } else {
+                    panic!("`item` is null.")
+                }
+            },
+        ),
+    };
+
+    // `<name>_commit` takes every argument except the slice, and passes the staged items in its place
+    let mut commit = input.clone();
+    commit.attrs.clear();
+    commit.sig.ident = format_ident!("{}_commit", name);
+    commit.sig.inputs = input
+        .sig
+        .inputs
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != index)
+        .map(|(_, arg)| arg.clone())
+        .collect();
+
+    let call_args = input.sig.inputs.iter().enumerate().map(|(i, arg)| {
+        if i == index {
+            return quote! { &staged };
+        }
+
+        let FnArg::Typed(pattern) = arg else {
+            unreachable!()
+        };
+        let arg_name = &pattern.pat;
+
+        // The conversion of `expand_game_function` turns strings into a `Cow<str>`
+        if pattern.ty.to_token_stream().to_string() == "String" {
+            quote! { #arg_name.to_string() }
+        } else {
+            quote! { #arg_name }
+        }
+    });
+
+    commit.block = Box::new(parse_quote! {{
+        let staged = #staging.with(|x| {
+            grug_rs::alloc_tracking::untrack(x as *const _ as usize);
+            std::mem::take(&mut *x.borrow_mut())
+        });
+        #name(#(#call_args),*)
+    }});
+
+    let mut output = TokenStream::from(quote! {
+        #input
+
+        thread_local! {
+            static #staging: std::cell::RefCell<Vec<#element>> = const { std::cell::RefCell::new(Vec::new()) };
+        }
+
+        #[unsafe(no_mangle)]
+        unsafe extern "C" fn #begin() {
+            #staging.with(|x| {
+                grug_rs::alloc_tracking::untrack(x as *const _ as usize);
+                x.borrow_mut().clear();
+            });
+        }
+
+        #[unsafe(no_mangle)]
+        unsafe extern "C" fn #push(item: #item_type) {
+            let item = #to_element;
+            #staging.with(|x| {
+                let mut staged = x.borrow_mut();
+                staged.push(item);
+                grug_rs::alloc_tracking::track_staging(
+                    x as *const _ as usize,
+                    #name_string,
+                    staged.len() * std::mem::size_of::<#element>(),
+                );
+            });
+        }
+    });
+
+    output.extend(register_signature(
+        &begin.to_string()["game_fn_".len()..],
+        &[],
+        "void",
+    ));
+    output.extend(register_signature(
+        &push.to_string()["game_fn_".len()..],
+        &[item_kind],
+        "void",
+    ));
+    output.extend(expand_game_function(commit));
+
+    output
+}
+
+/// Generates the game functions scripts use to read a table published with `Grug::publish_table`
+///
+/// `grug_table!(items)` creates `items_get_i32(id, column)`, `items_get_f32(id, column)`,
+/// `items_get_bool(id, column)`, `items_get_string(id, column)` and `items_has(id)`.
+/// Their `mod_api.json` entries can be added with `ModAPI::add_table`.
+///
+/// # Example
+/// ```
+/// grug_table!(items);
+/// ```
+#[proc_macro]
+pub fn grug_table(item: TokenStream) -> TokenStream {
+    let name = parse_macro_input!(item as Ident);
+    let table = name.to_string();
+
+    let get_i32 = format_ident!("game_fn_{}_get_i32", name);
+    let get_f32 = format_ident!("game_fn_{}_get_f32", name);
+    let get_bool = format_ident!("game_fn_{}_get_bool", name);
+    let get_string = format_ident!("game_fn_{}_get_string", name);
+    let has = format_ident!("game_fn_{}_has", name);
+
+    let arguments = [literal("i32"), literal("string")];
+    let mut output = TokenStream::new();
+    for (suffix, return_type) in [
+        ("get_i32", "i32"),
+        ("get_f32", "f32"),
+        ("get_bool", "bool"),
+        ("get_string", "string"),
+    ] {
+        output.extend(register_signature(
+            &format!("{table}_{suffix}"),
+            &arguments,
+            return_type,
+        ));
+    }
+    output.extend(register_signature(
+        &format!("{table}_has"),
+        &[literal("i32")],
+        "bool",
+    ));
+
+    output.extend(TokenStream::from(quote! {
+        #[unsafe(no_mangle)]
+        unsafe extern "C" fn #get_i32(id: i32, column: *const std::ffi::c_char) -> i32 {
+            unsafe { grug_rs::table::get_i32(#table, id, column) }
+        }
+
+        #[unsafe(no_mangle)]
+        unsafe extern "C" fn #get_f32(id: i32, column: *const std::ffi::c_char) -> f32 {
+            unsafe { grug_rs::table::get_f32(#table, id, column) }
+        }
+
+        #[unsafe(no_mangle)]
+        unsafe extern "C" fn #get_bool(id: i32, column: *const std::ffi::c_char) -> bool {
+            unsafe { grug_rs::table::get_bool(#table, id, column) }
+        }
+
+        #[unsafe(no_mangle)]
+        unsafe extern "C" fn #get_string(
+            id: i32,
+            column: *const std::ffi::c_char,
+        ) -> *const std::ffi::c_char {
+            unsafe { grug_rs::table::get_string(#table, id, column) }
+        }
+
+        #[unsafe(no_mangle)]
+        unsafe extern "C" fn #has(id: i32) -> bool {
+            grug_rs::table::has(#table, id)
+        }
+    }));
+
+    output
+}
+
+/// Derives `grug_rs::GrugStruct`, so the struct can be passed to on_functions and taken by game functions by value
+///
+/// The struct has to be `#[repr(C)]` and `Copy`.
+///
+/// # Example
+/// ```
+/// #[derive(GrugStruct, Clone, Copy)]
+/// #[repr(C)]
+/// struct Vec2 {
+///     x: f32,
+///     y: f32,
+/// }
+/// ```
+#[proc_macro_derive(GrugStruct)]
+pub fn derive_grug_struct(item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+    let name = &input.ident;
+
+    let is_repr_c = input.attrs.iter().any(|attr| {
+        attr.path().is_ident("repr") && attr.to_token_stream().to_string().contains("C")
+    });
+    if !is_repr_c {
+        return TokenStream::from(
+            Error::new_spanned(
+                name,
+                "`GrugStruct` can only be derived for `#[repr(C)]` structs",
+            )
+            .to_compile_error(),
+        );
+    }
+
+    if !matches!(input.data, Data::Struct(_)) {
+        return TokenStream::from(
+            Error::new_spanned(name, "`GrugStruct` can only be derived for structs")
+                .to_compile_error(),
+        );
+    }
+
+    TokenStream::from(quote! {
+        impl grug_rs::GrugStruct for #name {}
+
+        impl From<#name> for grug_rs::GrugValue<'static> {
+            fn from(value: #name) -> Self {
+                grug_rs::GrugValue::Custom(grug_rs::CustomValue::owned(value))
+            }
+        }
     })
 }
@@ -0,0 +1,80 @@
+//! Keeps dlls compiled by another version of grug from getting loaded
+//!
+//! grug doesn't put a version in the dlls it compiles, and happily loads a dll cache left behind
+//! by an older version of the game. So grug-rs writes the version of the grug it links next to the dlls,
+//! and throws the dlls away when it doesn't match.
+
+use std::{
+    fs::{create_dir_all, read_dir, read_to_string, remove_file, write},
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+use crate::{Grug, GrugError};
+
+const ABI_MARKER_NAME: &str = "grug_abi";
+
+/// What the dlls in a dll folder get marked with, the version of the grug-sys that compiles them
+///
+/// Only grug decides what ends up in the dlls, so updating grug-rs alone keeps them.
+pub const ABI_VERSION: &str = concat!("grug-sys ", env!("GRUG_SYS_VERSION"));
+
+/// The dll folder held dlls compiled by another version, which got removed so they get recompiled
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("The dlls in `{dll_folder}` were compiled by `{found}` instead of `{expected}`")]
+pub struct AbiMismatch {
+    pub dll_folder: PathBuf,
+    pub found: String,
+    pub expected: String,
+    pub removed_dlls: usize,
+}
+
+impl Grug {
+    /// Whether the dll folder had to be cleared on startup, because its dlls came from another version
+    pub fn abi_mismatch(&self) -> Option<&AbiMismatch> {
+        self.abi_mismatch.as_ref()
+    }
+}
+
+/// Removes every dll in `dll_folder` if they weren't compiled by this version, and marks the folder with this version
+pub(crate) fn check_dll_folder(dll_folder: &Path) -> Result<Option<AbiMismatch>, GrugError> {
+    let marker_path = dll_folder.join(ABI_MARKER_NAME);
+    let found = read_to_string(&marker_path).ok();
+
+    if found.as_deref() == Some(ABI_VERSION) {
+        return Ok(None);
+    }
+
+    let mut removed_dlls = 0;
+    remove_dlls(dll_folder, &mut removed_dlls);
+
+    create_dir_all(dll_folder)
+        .and_then(|_| write(&marker_path, ABI_VERSION))
+        .map_err(|x| GrugError::WriteAbiMarker {
+            path: marker_path,
+            error: x.to_string(),
+        })?;
+
+    if removed_dlls == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(AbiMismatch {
+        dll_folder: dll_folder.to_path_buf(),
+        found: found.unwrap_or_else(|| "an unknown version".to_string()),
+        expected: ABI_VERSION.to_string(),
+        removed_dlls,
+    }))
+}
+
+pub(crate) fn remove_dlls(dir: &Path, removed_dlls: &mut usize) {
+    for entry in read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            remove_dlls(&path, removed_dlls);
+        } else if path.extension().is_some_and(|x| x == "so") && remove_file(&path).is_ok() {
+            *removed_dlls += 1;
+        }
+    }
+}
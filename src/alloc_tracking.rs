@@ -0,0 +1,101 @@
+//! Keeps track of every buffer grug-rs allocates on behalf of scripts, with the `alloc-tracking` feature
+//!
+//! Anything still alive when `Grug` gets dropped is printed along with the file that owned it,
+//! followed by a panic, so leaks make tests fail.
+//! Without the feature, or with the `release-unchecked` feature, tracking does nothing.
+
+use crate::ffi_string::display_ptr;
+
+#[cfg(all(feature = "alloc-tracking", not(feature = "release-unchecked")))]
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllocationKind {
+    /// The globals of a file, for a single on_function call
+    Globals,
+    /// A C string kept alive by `Arguments`
+    String,
+    /// Items pushed to the staging buffer of a game function taking a slice, which haven't been committed
+    Staging,
+}
+
+#[derive(Debug, Clone)]
+pub struct TrackedAllocation {
+    pub kind: AllocationKind,
+    /// The file, or the game function and file, that the buffer was allocated for
+    pub owner: String,
+    pub size: usize,
+}
+
+#[cfg(all(feature = "alloc-tracking", not(feature = "release-unchecked")))]
+static LIVE: LazyLock<Mutex<HashMap<usize, TrackedAllocation>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Starts tracking the buffer at `address`, replacing what was tracked there before
+#[doc(hidden)]
+pub fn track(address: usize, kind: AllocationKind, owner: impl FnOnce() -> String, size: usize) {
+    #[cfg(all(feature = "alloc-tracking", not(feature = "release-unchecked")))]
+    LIVE.lock().unwrap().insert(
+        address,
+        TrackedAllocation {
+            kind,
+            owner: owner(),
+            size,
+        },
+    );
+    #[cfg(not(all(feature = "alloc-tracking", not(feature = "release-unchecked"))))]
+    let _ = (address, kind, owner, size);
+}
+
+/// Tracks the staging buffer of `game_function` at `address`, owned by the file that's running
+#[doc(hidden)]
+pub fn track_staging(address: usize, game_function: &str, size: usize) {
+    track(
+        address,
+        AllocationKind::Staging,
+        || {
+            // SAFETY: This implements the copy trait so it's safe to use
+            let path = unsafe { grug_sys::grug_fn_path };
+            let path = unsafe { display_ptr(path, "<unknown file>") };
+            format!("`{game_function}` in {path}")
+        },
+        size,
+    );
+}
+
+/// Stops tracking the buffer at `address`, once it's freed
+#[doc(hidden)]
+pub fn untrack(address: usize) {
+    #[cfg(all(feature = "alloc-tracking", not(feature = "release-unchecked")))]
+    LIVE.lock().unwrap().remove(&address);
+    #[cfg(not(all(feature = "alloc-tracking", not(feature = "release-unchecked"))))]
+    let _ = address;
+}
+
+/// Every tracked buffer that is still alive, always empty without the `alloc-tracking` feature
+pub fn live_allocations() -> Vec<TrackedAllocation> {
+    #[cfg(all(feature = "alloc-tracking", not(feature = "release-unchecked")))]
+    return LIVE.lock().unwrap().values().cloned().collect();
+    #[cfg(not(all(feature = "alloc-tracking", not(feature = "release-unchecked"))))]
+    vec![]
+}
+
+/// Prints every buffer that is still alive and panics, if there are any
+#[cfg(all(feature = "alloc-tracking", not(feature = "release-unchecked")))]
+pub(crate) fn check_for_leaks() {
+    let leaks = live_allocations();
+    if leaks.is_empty() || std::thread::panicking() {
+        return;
+    }
+
+    for leak in &leaks {
+        eprintln!(
+            "Leaked {:?} buffer of {} bytes, owned by {}",
+            leak.kind, leak.size, leak.owner
+        );
+    }
+    panic!("grug-rs leaked {} buffers", leaks.len());
+}
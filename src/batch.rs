@@ -0,0 +1,56 @@
+//! Calling the same on_function once per item, like `on_hit` for every target of an explosion
+
+use crate::{Arguments, Grug, GrugError, GrugValue};
+
+impl Grug {
+    /// Calls an on_function of every file of `entity_name` once per item of `items`, in order
+    ///
+    /// The mods get regenerated and the files looked up once, and the values of every item
+    /// get marshalled into the same buffers, instead of starting over for every call.
+    /// Otherwise it works like `activate_on_function`, and does nothing while grug is paused.
+    ///
+    /// # Example
+    /// ```rs
+    /// grug.activate_for_each(
+    ///     "Explosion",
+    ///     "on_hit",
+    ///     targets.iter_mut().map(|x| vec![GrugValue::custom(x)]),
+    /// )?;
+    /// ```
+    ///
+    /// # Safety
+    /// Undefined behavior if arguments passed in are incorrect
+    pub fn activate_for_each<'a, S1, S2, I>(
+        &self,
+        entity_name: S1,
+        on_function_name: S2,
+        items: I,
+    ) -> Result<(), GrugError>
+    where
+        S1: ToString,
+        S2: ToString,
+        I: IntoIterator<Item = Vec<GrugValue<'a>>>,
+    {
+        if self.is_paused() {
+            return Ok(());
+        }
+
+        let entity_name = entity_name.to_string();
+        let on_function_name = on_function_name.to_string();
+
+        let index = self.prepare_on_function(&entity_name, &on_function_name)?;
+
+        let files = self.get_files_and_mods_by_entity_type(entity_name);
+
+        let mut arguments = Arguments::empty();
+        for values in items {
+            arguments.set_values(values);
+
+            for (mod_name, file) in &files {
+                unsafe { self.run_file(mod_name, file, index, &on_function_name, &mut arguments)? };
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,63 @@
+//! Sending an on_function to every entity that has it, like `on_load` at startup
+
+use std::collections::HashMap;
+
+use crate::{Arguments, Grug, GrugError, ffi_string::bytes_from_ptr, headless};
+
+impl Grug {
+    /// Activates an on_function on every entity type whose `mod_api.json` entry has it.
+    ///
+    /// Files run in mod order, so every file of the first mod runs before any file of the second.
+    /// Otherwise it works like `activate_on_function`, and does nothing while grug is paused.
+    ///
+    /// # Example
+    /// ```rs
+    /// grug.broadcast_all_entities("on_load", &mut Arguments::empty())?;
+    /// ```
+    pub fn broadcast_all_entities<S: ToString>(
+        &self,
+        on_function_name: S,
+        arguments: &mut Arguments,
+    ) -> Result<(), GrugError> {
+        if self.is_paused() {
+            return Ok(());
+        }
+
+        let on_function_name = on_function_name.to_string();
+
+        let indices: HashMap<&str, usize> = self
+            .entities
+            .iter()
+            .filter_map(|(entity, on_functions)| {
+                let index = on_functions.get(&on_function_name)?;
+                Some((entity.as_str(), *index))
+            })
+            .collect();
+
+        if indices.is_empty() {
+            return Err(GrugError::NotAnOnFunction {
+                function_name: on_function_name,
+            });
+        }
+
+        for entity in indices.keys() {
+            if !self.is_entity_loaded(entity) {
+                self.preload_entity(entity)?;
+            }
+        }
+        if headless::should_regenerate() {
+            self.regenerate_modified_mods()?;
+        }
+
+        for (mod_name, file) in self.get_files_and_mods() {
+            let entity_type = unsafe { bytes_from_ptr(file.inner.entity_type) }
+                .and_then(|x| std::str::from_utf8(x).ok());
+
+            if let Some(&index) = entity_type.and_then(|x| indices.get(x)) {
+                unsafe { self.run_file(mod_name, &file, index, &on_function_name, arguments)? };
+            }
+        }
+
+        Ok(())
+    }
+}
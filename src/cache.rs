@@ -0,0 +1,139 @@
+//! Manifest in the dll folder that remembers which source every dll was compiled from
+//!
+//! grug decides whether to recompile a file by comparing modification times,
+//! so anything that touches the mods without changing them (reinstalling, copying, syncing)
+//! makes it recompile everything. The manifest lets unchanged files skip that.
+
+use std::{
+    fs::{File, read_to_string, write},
+    mem::take,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use linked_hash_map::LinkedHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{Grug, compile::ModFile, hash::hash_file};
+
+/// Name of the manifest inside the dll folder
+pub const MANIFEST_NAME: &str = "grug_cache.json";
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct CacheManifest {
+    /// Keyed by the path of the grug file, relative to the mods folder
+    pub entries: LinkedHashMap<String, CacheEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CacheEntry {
+    /// Hash of the grug source the dll was compiled from
+    pub hash: String,
+    pub dll_path: PathBuf,
+}
+
+/// Why a manifest entry no longer matches what's on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StaleReason {
+    SourceMissing,
+    SourceChanged,
+    DllMissing,
+}
+
+#[derive(Debug, Clone)]
+pub struct StaleCacheEntry {
+    pub grug_path: PathBuf,
+    pub reason: StaleReason,
+}
+
+impl CacheManifest {
+    /// Loads the manifest from `mods_dll_folder`, a missing or broken manifest is treated as empty
+    pub fn load(mods_dll_folder: &Path) -> Self {
+        read_to_string(mods_dll_folder.join(MANIFEST_NAME))
+            .ok()
+            .and_then(|x| serde_json::from_str(&x).ok())
+            .unwrap_or_default()
+    }
+
+    /// Writes the manifest to `mods_dll_folder`
+    ///
+    /// Failing to write it only costs a recompile next time, so errors are ignored.
+    pub fn save(&self, mods_dll_folder: &Path) {
+        if let Ok(json) = serde_json::to_string_pretty(self) {
+            let _ = write(mods_dll_folder.join(MANIFEST_NAME), json);
+        }
+    }
+
+    /// If the dll of `file` was compiled from its current source, marks the dll as up to date
+    /// so grug won't recompile it.
+    ///
+    /// Returns whether it did.
+    pub(crate) fn refresh(&self, file: &ModFile) -> bool {
+        let Some(entry) = self.entries.get(&key(file)) else {
+            return false;
+        };
+
+        if hash_file(&file.grug_path).as_ref() != Some(&entry.hash) {
+            return false;
+        }
+
+        File::options()
+            .write(true)
+            .open(&file.dll_path)
+            .and_then(|x| x.set_modified(SystemTime::now()))
+            .is_ok()
+    }
+
+    /// Records the current source of every file with an up to date dll,
+    /// and forgets about files that don't exist anymore.
+    pub(crate) fn update(&mut self, mods_folder: &Path, files: &[ModFile]) {
+        for file in files.iter().filter(|x| !x.is_stale()) {
+            if let Some(hash) = hash_file(&file.grug_path) {
+                self.entries.insert(
+                    key(file),
+                    CacheEntry {
+                        hash,
+                        dll_path: file.dll_path.clone(),
+                    },
+                );
+            }
+        }
+
+        self.entries = take(&mut self.entries)
+            .into_iter()
+            .filter(|(relative_path, entry)| {
+                mods_folder.join(relative_path).is_file() && entry.dll_path.is_file()
+            })
+            .collect();
+    }
+}
+
+impl Grug {
+    /// Checks every entry of the cache manifest against the files on disk.
+    ///
+    /// Stale entries are harmless, they just get recompiled by the next `compile_all_mods`.
+    pub fn verify_cache(&self) -> Vec<StaleCacheEntry> {
+        let manifest = CacheManifest::load(&self.mods_dll_folder);
+
+        manifest
+            .entries
+            .iter()
+            .filter_map(|(relative_path, entry)| {
+                let grug_path = self.mods_folder.join(relative_path);
+
+                let reason = match hash_file(&grug_path) {
+                    None => StaleReason::SourceMissing,
+                    Some(hash) if hash != entry.hash => StaleReason::SourceChanged,
+                    Some(_) if !entry.dll_path.is_file() => StaleReason::DllMissing,
+                    Some(_) => return None,
+                };
+
+                Some(StaleCacheEntry { grug_path, reason })
+            })
+            .collect()
+    }
+}
+
+fn key(file: &ModFile) -> String {
+    file.relative_path.to_string_lossy().to_string()
+}
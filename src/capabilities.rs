@@ -0,0 +1,84 @@
+//! What the linked grug build supports, for code that has to work across versions
+
+/// The version of the scripting backend
+///
+/// grug doesn't report a version of its own, so this is the version of the grug-sys grug-rs links.
+/// It's the same version the dll folder gets marked with.
+pub fn backend_version() -> &'static str {
+    crate::abi::ABI_VERSION
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    /// On_functions can be switched to fast mode, which skips the runtime error checks
+    pub fast_mode: bool,
+    /// Mods can run without being compiled to dlls first
+    pub interpreter: bool,
+    /// Mods can be dumped to, and generated from, json
+    pub ast_json: bool,
+    /// The most arguments `activate_on_function` can pass
+    pub max_on_function_arguments: usize,
+    /// The most arguments a script can pass in a single call
+    pub max_call_arguments: usize,
+    /// The grug-rs features this build was compiled with
+    pub features: Vec<&'static str>,
+}
+
+/// Describes what the linked grug build and grug-rs support
+pub fn capabilities() -> Capabilities {
+    let mut features = vec![];
+    if cfg!(feature = "compile") {
+        features.push("compile");
+    }
+    if cfg!(feature = "cache") {
+        features.push("cache");
+    }
+    if cfg!(feature = "abort") {
+        features.push("abort");
+    }
+    if cfg!(feature = "commands") {
+        features.push("commands");
+    }
+    if cfg!(feature = "constants") {
+        features.push("constants");
+    }
+    if cfg!(feature = "events") {
+        features.push("events");
+    }
+    if cfg!(feature = "random") {
+        features.push("random");
+    }
+    if cfg!(feature = "settings") {
+        features.push("settings");
+    }
+    if cfg!(feature = "store") {
+        features.push("store");
+    }
+    if cfg!(feature = "release-unchecked") {
+        features.push("release-unchecked");
+    }
+    // `release-unchecked` compiles these out, even when they're enabled
+    if cfg!(all(
+        feature = "borrow-check",
+        not(feature = "release-unchecked")
+    )) {
+        features.push("borrow-check");
+    }
+    if cfg!(all(
+        feature = "alloc-tracking",
+        not(feature = "release-unchecked")
+    )) {
+        features.push("alloc-tracking");
+    }
+
+    Capabilities {
+        fast_mode: true,
+        interpreter: false,
+        ast_json: true,
+        // The arities `GrugFile::run_on_function` has calls generated for
+        max_on_function_arguments: 2,
+        // One less than grug's MAX_CALL_ARGUMENTS_PER_STACK_FRAME
+        max_call_arguments: 68,
+        features,
+    }
+}
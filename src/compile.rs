@@ -0,0 +1,293 @@
+//! Compiling grug files one at a time, so progress can be reported in between them
+
+use std::{
+    collections::HashSet,
+    ffi::{CString, OsStr, c_char},
+    fs::{create_dir_all, metadata, read_dir},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    slice::from_raw_parts,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use grug_sys::{grug_mod_dir, grug_mods};
+
+#[cfg(feature = "cache")]
+use crate::cache::CacheManifest;
+use crate::{Grug, GrugError, ffi_string::bytes_from_ptr, walk::collect_grug_files};
+
+/// Gets called with `files_done`, `files_total` and the file that just got compiled
+pub type ProgressCallback = Box<dyn Fn(usize, usize, &Path) + Send + Sync>;
+
+unsafe extern "C" {
+    // Not in `grug.h`, grug only exports it for its own test suite.
+    // It compiles a single grug file into its dll without loading it.
+    fn grug_test_regenerate_dll(
+        grug_path: *const c_char,
+        dll_path: *const c_char,
+        mod_name: *const c_char,
+    ) -> bool;
+}
+
+/// Cancels `compile_all_mods_with` in between two files
+///
+/// Cloning it gives a handle to the same token, so it can be cancelled from another thread
+/// or from inside the progress callback.
+#[derive(Clone, Default, Debug)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// A grug file in the mods folder, along with where grug puts its dll
+pub(crate) struct ModFile {
+    pub mod_name: String,
+    /// Relative to the mods folder
+    #[cfg_attr(not(feature = "cache"), allow(dead_code))]
+    pub relative_path: PathBuf,
+    pub grug_path: PathBuf,
+    pub dll_path: PathBuf,
+}
+
+impl ModFile {
+    /// Uses the same rule as grug: a dll is stale if it doesn't exist or is older than its grug file
+    pub fn is_stale(&self) -> bool {
+        let grug_modified = metadata(&self.grug_path).and_then(|x| x.modified());
+        let dll_modified = metadata(&self.dll_path).and_then(|x| x.modified());
+
+        match (grug_modified, dll_modified) {
+            (Ok(grug_modified), Ok(dll_modified)) => grug_modified > dll_modified,
+            _ => true,
+        }
+    }
+
+    /// Compiles the file into its dll
+    ///
+    /// Returns whether it succeeded, the actual error gets reported by grug once it loads the file.
+    pub fn compile(&self) -> bool {
+        if let Some(parent) = self.dll_path.parent() {
+            let _ = create_dir_all(parent);
+        }
+
+        let grug_path = CString::new(self.grug_path.to_string_lossy().to_string()).unwrap();
+        let dll_path = CString::new(self.dll_path.to_string_lossy().to_string()).unwrap();
+        let mod_name = CString::new(self.mod_name.clone()).unwrap();
+
+        let failed = unsafe {
+            grug_test_regenerate_dll(grug_path.as_ptr(), dll_path.as_ptr(), mod_name.as_ptr())
+        };
+
+        !failed
+    }
+}
+
+impl Grug {
+    /// Sets a callback that gets called after every compiled file.
+    ///
+    /// Useful for drawing a loading bar while a big mod pack compiles.
+    ///
+    /// # Example
+    /// ```rs
+    /// grug.set_progress_callback(|done, total, file| {
+    ///     println!("[{done}/{total}] {}", file.display());
+    /// });
+    /// grug.compile_all_mods()?;
+    /// ```
+    pub fn set_progress_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(usize, usize, &Path) + Send + Sync + 'static,
+    {
+        self.progress_callback = Some(Box::new(callback));
+    }
+
+    /// Compiles and loads every mod, calling the progress callback after every file.
+    ///
+    /// Meant to be called once behind a loading screen, before the first `activate_on_function`.
+    pub fn compile_all_mods(&self) -> Result<(), GrugError> {
+        self.compile_all_mods_with(&CancelToken::new())
+    }
+
+    /// Same as `compile_all_mods`, but checks `cancel_token` in between files.
+    ///
+    /// Once cancelled it returns `GrugError::Cancelled` without loading anything.
+    /// The files that did get compiled stay compiled, so the next call picks up where this one left off.
+    ///
+    /// # Example
+    /// ```rs
+    /// let cancel_token = CancelToken::new();
+    ///
+    /// let token = cancel_token.clone();
+    /// grug.set_progress_callback(move |_, _, _| {
+    ///     if player_pressed_escape() {
+    ///         token.cancel();
+    ///     }
+    /// });
+    ///
+    /// match grug.compile_all_mods_with(&cancel_token) {
+    ///     Err(GrugError::Cancelled { .. }) => return_to_main_menu(),
+    ///     result => result?,
+    /// }
+    /// ```
+    pub fn compile_all_mods_with(&self, cancel_token: &CancelToken) -> Result<(), GrugError> {
+        self.sync_lazy_mods()?;
+        self.compile_stale_files(cancel_token)?;
+
+        unsafe { Self::regenerate_modified_mods_unchecked() }
+    }
+
+    /// Compiles every stale file that grug hasn't loaded yet, one at a time.
+    ///
+    /// With the `cache` feature, files whose source still matches the cache manifest are not recompiled,
+    /// their dll just gets marked as up to date again.
+    ///
+    /// Stops at the first file that fails to compile, since grug reports that error itself afterwards.
+    pub(crate) fn compile_stale_files(&self, cancel_token: &CancelToken) -> Result<(), GrugError> {
+        let files = self.find_unloaded_files();
+        let stale_files: Vec<&ModFile> = files.iter().filter(|file| file.is_stale()).collect();
+
+        #[cfg(feature = "cache")]
+        let mut manifest = CacheManifest::load(&self.mods_dll_folder);
+        #[cfg(feature = "cache")]
+        let stale_files: Vec<&ModFile> = stale_files
+            .into_iter()
+            .filter(|file| !manifest.refresh(file))
+            .collect();
+
+        let total = stale_files.len();
+        let mut compiled = vec![];
+        let mut cancelled = false;
+
+        for (i, file) in stale_files.iter().enumerate() {
+            if cancel_token.is_cancelled() {
+                cancelled = true;
+                break;
+            }
+
+            if !file.compile() {
+                break;
+            }
+            compiled.push(file.grug_path.clone());
+
+            if let Some(callback) = &self.progress_callback {
+                callback(i + 1, total, &file.grug_path);
+            }
+        }
+
+        #[cfg(feature = "cache")]
+        {
+            manifest.update(&self.mods_folder, &files);
+            manifest.save(&self.mods_dll_folder);
+        }
+
+        if cancelled {
+            return Err(GrugError::Cancelled {
+                remaining: total - compiled.len(),
+                compiled,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Finds every grug file grug hasn't loaded yet.
+    ///
+    /// Loaded files are left alone, since grug only reloads a loaded file
+    /// when it compiles the file itself.
+    pub(crate) fn find_unloaded_files(&self) -> Vec<ModFile> {
+        let loaded = loaded_files();
+        let mut files = vec![];
+
+        for entry in read_dir(&self.mods_folder).into_iter().flatten().flatten() {
+            let path = entry.path();
+            if !path.is_dir() {
+                continue;
+            }
+
+            let mod_name = entry.file_name().to_string_lossy().to_string();
+            let mut grug_paths = vec![];
+            collect_grug_files(&path, &mut grug_paths);
+
+            for grug_path in grug_paths {
+                let relative_path = grug_path.strip_prefix(&self.mods_folder).unwrap();
+                if loaded.contains(relative_path) {
+                    continue;
+                }
+
+                files.push(ModFile {
+                    mod_name: mod_name.clone(),
+                    relative_path: relative_path.to_path_buf(),
+                    dll_path: self
+                        .mods_dll_folder
+                        .join(relative_path)
+                        .with_extension("so"),
+                    grug_path,
+                });
+            }
+        }
+
+        files
+    }
+}
+
+/// Paths of every file grug has loaded, relative to the mods folder
+fn loaded_files() -> HashSet<PathBuf> {
+    #[allow(static_mut_refs)]
+    let mods = unsafe { grug_mods }; // SAFETY: This implements the copy trait so it's safe to use
+
+    let mut loaded = HashSet::new();
+    for dir in unsafe { dirs_of(&mods) } {
+        collect_loaded_files(dir, PathBuf::new(), &mut loaded);
+    }
+
+    loaded
+}
+
+fn collect_loaded_files(dir: &grug_mod_dir, parent: PathBuf, loaded: &mut HashSet<PathBuf>) {
+    let path = parent.join(unsafe { path_from_ptr(dir.name) });
+
+    if !dir.files.is_null() {
+        for file in unsafe { from_raw_parts(dir.files, dir.files_size) } {
+            loaded.insert(path.join(unsafe { path_from_ptr(file.name) }));
+        }
+    }
+
+    for subdir in unsafe { dirs_of(dir) } {
+        collect_loaded_files(subdir, path.clone(), loaded);
+    }
+}
+
+/// File names can be any bytes, so they're read without assuming UTF-8
+///
+/// # Safety
+/// `ptr` has to come from grug
+unsafe fn path_from_ptr<'a>(ptr: *const c_char) -> &'a Path {
+    Path::new(OsStr::from_bytes(
+        unsafe { bytes_from_ptr(ptr) }.unwrap_or_default(),
+    ))
+}
+
+/// # Safety
+/// `dir` has to come from grug
+unsafe fn dirs_of(dir: &grug_mod_dir) -> &[grug_mod_dir] {
+    if dir.dirs.is_null() {
+        &[]
+    } else {
+        unsafe { from_raw_parts(dir.dirs, dir.dirs_size) }
+    }
+}
@@ -0,0 +1,112 @@
+//! What players paste into bug reports when a modded game crashes
+//!
+//! # Example
+//! ```rs
+//! std::panic::set_hook(Box::new(move |info| {
+//!     eprintln!("{info}\n\nMods: {}", grug.crash_report_blob());
+//! }));
+//! ```
+
+use std::sync::{Mutex, MutexGuard};
+
+use serde::Serialize;
+
+use crate::{Grug, abi::ABI_VERSION, snapshot::RuntimeErrorRecord};
+
+/// How many on_function calls crash reports hold on to
+const RECENT_CALLS_CAPACITY: usize = 16;
+
+/// The last on_function calls, which get overwritten in place so recording them doesn't allocate
+static RECENT_CALLS: Mutex<RecentCalls> = Mutex::new(RecentCalls {
+    calls: vec![],
+    next: 0,
+});
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CallRecord {
+    #[serde(rename = "mod")]
+    pub mod_name: String,
+    pub file: String,
+    pub on_function: String,
+}
+
+struct RecentCalls {
+    calls: Vec<CallRecord>,
+    /// Where the next call goes once `calls` is full
+    next: usize,
+}
+
+#[derive(Serialize)]
+struct CrashReport<'a> {
+    /// The grug-sys version, see `backend_version`
+    backend: &'a str,
+    mods: Vec<ModEntry>,
+    recent_calls: Vec<CallRecord>,
+    recent_errors: Vec<RuntimeErrorRecord>,
+}
+
+#[derive(Serialize)]
+struct ModEntry {
+    name: String,
+    version: Option<String>,
+    content_hash: Option<String>,
+}
+
+impl Grug {
+    /// Compact json with the loaded mods, their versions and content hashes,
+    /// and the last on_function calls and runtime errors, oldest first
+    pub fn crash_report_blob(&self) -> String {
+        let snapshot = self.snapshot();
+
+        let mods = snapshot
+            .mods
+            .into_iter()
+            .map(|x| ModEntry {
+                content_hash: self.export_manifest(&x.name).ok().map(|x| x.content_hash),
+                name: x.name,
+                version: x.version,
+            })
+            .collect();
+
+        let recent_calls = {
+            let recent_calls = lock(&RECENT_CALLS);
+            let (newer, older) = recent_calls.calls.split_at(recent_calls.next);
+            older.iter().chain(newer).cloned().collect()
+        };
+
+        let report = CrashReport {
+            backend: ABI_VERSION,
+            mods,
+            recent_calls,
+            recent_errors: snapshot.recent_errors,
+        };
+
+        serde_json::to_string(&report).unwrap()
+    }
+}
+
+/// Called before every on_function runs
+pub(crate) fn record_call(mod_name: &[u8], file_name: &[u8], on_function: &str) {
+    let mut recent_calls = lock(&RECENT_CALLS);
+
+    if recent_calls.calls.len() < RECENT_CALLS_CAPACITY {
+        recent_calls.calls.push(CallRecord::default());
+    }
+    let index = recent_calls.next;
+    recent_calls.next = (index + 1) % RECENT_CALLS_CAPACITY;
+
+    let call = &mut recent_calls.calls[index];
+    overwrite(&mut call.mod_name, &String::from_utf8_lossy(mod_name));
+    overwrite(&mut call.file, &String::from_utf8_lossy(file_name));
+    overwrite(&mut call.on_function, on_function);
+}
+
+fn overwrite(string: &mut String, value: &str) {
+    string.clear();
+    string.push_str(value);
+}
+
+/// Crash reports are likely made while panicking, so a poisoned lock shouldn't cause another panic
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|x| x.into_inner())
+}
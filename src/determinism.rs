@@ -0,0 +1,59 @@
+//! Finds out which mods break lockstep multiplayer
+//!
+//! Game functions that read wall clocks, OS randomness and the like can be tagged with
+//! `#[game_function(nondeterministic)]`. Every call to one of them in between
+//! `begin_deterministic_step` and `end_deterministic_step` gets reported, along with the
+//! on_function and file that called it.
+
+use std::{
+    mem::take,
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use grug_sys::{grug_fn_name, grug_fn_path};
+
+use crate::{Grug, ffi_string::display_ptr};
+
+static IN_DETERMINISTIC_STEP: AtomicBool = AtomicBool::new(false);
+static REPORTS: Mutex<Vec<NondeterminismReport>> = Mutex::new(vec![]);
+
+/// A nondeterministic game function that got called during a deterministic step
+#[derive(Debug, Clone)]
+pub struct NondeterminismReport {
+    pub game_function: String,
+    pub on_function: String,
+    pub path: String,
+}
+
+impl Grug {
+    /// Starts recording calls to nondeterministic game functions
+    pub fn begin_deterministic_step(&self) {
+        IN_DETERMINISTIC_STEP.store(true, Ordering::Relaxed);
+    }
+
+    /// Stops recording, and returns every call made since `begin_deterministic_step`
+    pub fn end_deterministic_step(&self) -> Vec<NondeterminismReport> {
+        IN_DETERMINISTIC_STEP.store(false, Ordering::Relaxed);
+        take(&mut *REPORTS.lock().unwrap())
+    }
+}
+
+/// Called at the start of every game function tagged with `#[game_function(nondeterministic)]`
+#[doc(hidden)]
+pub fn note_nondeterministic_call(game_function: &str) {
+    if !IN_DETERMINISTIC_STEP.load(Ordering::Relaxed) {
+        return;
+    }
+
+    // SAFETY: These implement the copy trait so it's safe to use
+    let (on_function, path) = unsafe { (grug_fn_name, grug_fn_path) };
+
+    REPORTS.lock().unwrap().push(NondeterminismReport {
+        game_function: game_function.to_string(),
+        on_function: unsafe { display_ptr(on_function, "<unknown>") },
+        path: unsafe { display_ptr(path, "<unknown>") },
+    });
+}
@@ -0,0 +1,110 @@
+//! What happened to every file during a dispatch, instead of just whether it failed
+//!
+//! # Example
+//! ```rs
+//! let outcome = grug.activate_on_function_detailed("World", "on_update", &mut Arguments::empty())?;
+//! for file in outcome.errored() {
+//!     eprintln!("{}/{} failed", file.mod_name, file.file);
+//! }
+//! ```
+
+use std::time::Duration;
+
+use crate::{
+    Arguments, Grug, GrugError, ffi_string::display_ptr, perf_guard, snapshot::RuntimeErrorRecord,
+};
+
+#[derive(Debug, Clone)]
+pub enum FileStatus {
+    Executed,
+    /// The on_function had a runtime error
+    Errored(RuntimeErrorRecord),
+    /// The file doesn't define the on_function
+    Undefined,
+    /// The file was disabled with `Grug::set_file_enabled`
+    Disabled,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileOutcome {
+    pub mod_name: String,
+    pub file: String,
+    pub status: FileStatus,
+    /// How long the on_function ran for, only measured in debug builds and while `perf_guard` is recording,
+    /// and never with the `release-unchecked` feature
+    pub duration: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DispatchOutcome {
+    /// Every file of the entity type, in the order they were dispatched to
+    pub files: Vec<FileOutcome>,
+    /// Grug was paused, so nothing ran
+    pub paused: bool,
+}
+
+impl DispatchOutcome {
+    /// Whether no file had a runtime error
+    pub fn is_ok(&self) -> bool {
+        self.errored().next().is_none()
+    }
+
+    pub fn executed(&self) -> impl Iterator<Item = &FileOutcome> {
+        self.files
+            .iter()
+            .filter(|x| matches!(x.status, FileStatus::Executed))
+    }
+
+    pub fn errored(&self) -> impl Iterator<Item = &FileOutcome> {
+        self.files
+            .iter()
+            .filter(|x| matches!(x.status, FileStatus::Errored(_)))
+    }
+}
+
+impl Grug {
+    /// Like `activate_on_function`, but reports what happened to every file
+    ///
+    /// # Safety
+    /// Undefined behavior if arguments passed in are incorrect
+    pub fn activate_on_function_detailed<S1: ToString, S2: ToString>(
+        &self,
+        entity_name: S1,
+        on_function_name: S2,
+        arguments: &mut Arguments,
+    ) -> Result<DispatchOutcome, GrugError> {
+        if self.is_paused() {
+            return Ok(DispatchOutcome {
+                files: vec![],
+                paused: true,
+            });
+        }
+
+        let entity_name = entity_name.to_string();
+        let on_function_name = on_function_name.to_string();
+
+        let index = self.prepare_on_function(&entity_name, &on_function_name)?;
+
+        let timed = !cfg!(feature = "release-unchecked")
+            && (cfg!(debug_assertions) || perf_guard::is_recording());
+
+        let mut files = vec![];
+        for (mod_name, file) in self.get_files_and_mods_by_entity_type(entity_name) {
+            let (status, duration) =
+                unsafe { self.run_file(mod_name, &file, index, &on_function_name, arguments)? };
+
+            let ran = matches!(status, FileStatus::Executed | FileStatus::Errored(_));
+            files.push(FileOutcome {
+                mod_name: String::from_utf8_lossy(mod_name).to_string(),
+                file: unsafe { display_ptr(file.inner.name, "<unknown file>") },
+                status,
+                duration: (timed && ran).then_some(duration),
+            });
+        }
+
+        Ok(DispatchOutcome {
+            files,
+            paused: false,
+        })
+    }
+}
@@ -0,0 +1,112 @@
+//! Game functions backed by closures, which can be replaced or removed at runtime
+//!
+//! grug finds game functions by their symbol, so the symbol still comes from `#[game_function]`.
+//! Its body forwards to whichever closure is registered under its name:
+//! ```rs
+//! #[game_function]
+//! fn spawn_enemy(x: f32, y: f32) -> i32 {
+//!     grug_rs::dynamic::call("spawn_enemy", (x, y))
+//! }
+//!
+//! let world = world.clone();
+//! grug.register_game_function("spawn_enemy", move |(x, y): (f32, f32)| world.spawn(x, y))?;
+//! ```
+//!
+//! Closures are keyed by name rather than by mod, so regenerating mods never touches them.
+//! Replacing or unregistering a closure drops it right away, releasing anything it captured.
+//! If it's being called at that moment, it gets dropped once that call returns instead.
+
+use std::{
+    any::Any,
+    collections::HashMap,
+    sync::{Arc, LazyLock, Mutex},
+};
+
+use crate::{Grug, GrugError, game_function_error};
+
+type Registered<A, R> = Box<dyn Fn(A) -> R + Send + Sync>;
+
+static GAME_FUNCTIONS: LazyLock<Mutex<HashMap<String, Arc<dyn Any + Send + Sync>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn erase<A, R, F>(function: F) -> Arc<dyn Any + Send + Sync>
+where
+    A: 'static,
+    R: 'static,
+    F: Fn(A) -> R + Send + Sync + 'static,
+{
+    let function: Registered<A, R> = Box::new(function);
+    Arc::new(function)
+}
+
+impl Grug {
+    /// Registers the closure `call(name, ..)` forwards to
+    ///
+    /// Fails if something is already registered under `name`, use `replace_game_function` to swap it out.
+    pub fn register_game_function<S, A, R, F>(&self, name: S, function: F) -> Result<(), GrugError>
+    where
+        S: ToString,
+        A: 'static,
+        R: 'static,
+        F: Fn(A) -> R + Send + Sync + 'static,
+    {
+        let name = name.to_string();
+
+        let mut game_functions = GAME_FUNCTIONS.lock().unwrap();
+        if game_functions.contains_key(&name) {
+            return Err(GrugError::GameFunctionAlreadyRegistered { name });
+        }
+        game_functions.insert(name, erase(function));
+
+        Ok(())
+    }
+
+    /// Registers the closure `call(name, ..)` forwards to, dropping the one it replaces
+    ///
+    /// Returns whether there was one to replace.
+    pub fn replace_game_function<S, A, R, F>(&self, name: S, function: F) -> bool
+    where
+        S: ToString,
+        A: 'static,
+        R: 'static,
+        F: Fn(A) -> R + Send + Sync + 'static,
+    {
+        let old = GAME_FUNCTIONS
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), erase(function));
+
+        // Dropped after the lock is released, in case dropping a capture calls back into here
+        old.is_some()
+    }
+
+    /// Removes and drops the closure registered under `name`
+    ///
+    /// Scripts calling it afterwards get a runtime error. Returns whether there was one to remove.
+    pub fn unregister_game_function<S: ToString>(&self, name: S) -> bool {
+        let old = GAME_FUNCTIONS.lock().unwrap().remove(&name.to_string());
+
+        old.is_some()
+    }
+}
+
+/// Calls the closure registered under `name`
+///
+/// If there is none, or it takes different arguments, the script gets a runtime error
+/// and `R::default()` is returned.
+pub fn call<A: 'static, R: Default + 'static>(name: &str, args: A) -> R {
+    // Cloned out so the lock isn't held during the call
+    let function = GAME_FUNCTIONS.lock().unwrap().get(name).cloned();
+
+    let message = match function {
+        Some(function) => match function.downcast_ref::<Registered<A, R>>() {
+            Some(function) => return function(args),
+            None => format!("`{name}` was registered with different argument or return types"),
+        },
+        None => format!("`{name}` is not registered"),
+    };
+
+    game_function_error(&message);
+
+    R::default()
+}
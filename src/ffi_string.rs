@@ -0,0 +1,109 @@
+//! Reading strings that come from grug or mod dlls
+//!
+//! A buggy mod can hand over null pointers or bytes that aren't UTF-8,
+//! so every string read goes through here instead of `CStr::from_ptr`.
+
+use std::{
+    ffi::{CStr, c_char},
+    slice::from_raw_parts,
+};
+
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum FfiStringError {
+    #[error("is null")]
+    Null,
+    #[error("is not valid UTF-8: `{}`", bytes.escape_ascii())]
+    InvalidUtf8 {
+        /// The string without its NUL, so nothing gets lost
+        bytes: Vec<u8>,
+        valid_up_to: usize,
+    },
+}
+
+/// Reads the C string at `ptr`
+///
+/// # Safety
+/// `ptr` has to be null or point to a NUL terminated string that lives for `'a`
+pub unsafe fn str_from_ptr<'a>(ptr: *const c_char) -> Result<&'a str, FfiStringError> {
+    let bytes = unsafe { bytes_from_ptr(ptr) }.ok_or(FfiStringError::Null)?;
+
+    str_from_bytes(bytes)
+}
+
+/// Called by `#[game_function]` for every string argument
+///
+/// Same as `str_from_ptr`, but with the `release-unchecked` feature the string is assumed to be UTF-8.
+///
+/// # Safety
+/// `ptr` has to point to a NUL terminated string that lives for `'a`,
+/// and with `release-unchecked` it also has to be UTF-8
+#[doc(hidden)]
+pub unsafe fn str_from_argument<'a>(ptr: *const c_char) -> Result<&'a str, FfiStringError> {
+    #[cfg(feature = "release-unchecked")]
+    return Ok(unsafe { std::str::from_utf8_unchecked(CStr::from_ptr(ptr).to_bytes()) });
+    #[cfg(not(feature = "release-unchecked"))]
+    unsafe {
+        str_from_ptr(ptr)
+    }
+}
+
+/// Reads the bytes of the C string at `ptr`, without its NUL
+///
+/// # Safety
+/// `ptr` has to be null or point to a NUL terminated string that lives for `'a`
+pub unsafe fn bytes_from_ptr<'a>(ptr: *const c_char) -> Option<&'a [u8]> {
+    if ptr.is_null() {
+        return None;
+    }
+
+    Some(unsafe { CStr::from_ptr(ptr) }.to_bytes())
+}
+
+/// Reads a string out of a fixed size C array, like the ones in `grug_error`
+///
+/// Stops at the first NUL, or at the end of the array if there is none.
+pub fn str_from_array(array: &[c_char]) -> Result<&str, FfiStringError> {
+    // SAFETY: `c_char` and `u8` have the same layout
+    let bytes = unsafe { from_raw_parts(array.as_ptr().cast::<u8>(), array.len()) };
+    let len = bytes.iter().position(|x| *x == 0).unwrap_or(bytes.len());
+
+    str_from_bytes(&bytes[..len])
+}
+
+/// Same as `str_from_ptr`, but for strings that only get shown to people.
+///
+/// Null becomes `fallback`, and bytes that aren't UTF-8 get escaped instead of replaced.
+///
+/// # Safety
+/// `ptr` has to be null or point to a NUL terminated string
+pub unsafe fn display_ptr(ptr: *const c_char, fallback: &str) -> String {
+    match unsafe { bytes_from_ptr(ptr) } {
+        Some(bytes) => display_bytes(bytes),
+        None => fallback.to_string(),
+    }
+}
+
+/// Same as `str_from_array`, but for strings that only get shown to people
+pub fn display_array(array: &[c_char]) -> String {
+    match str_from_array(array) {
+        Ok(x) => x.to_string(),
+        Err(FfiStringError::InvalidUtf8 { bytes, .. }) => display_bytes(&bytes),
+        Err(FfiStringError::Null) => unreachable!(),
+    }
+}
+
+fn display_bytes(bytes: &[u8]) -> String {
+    match str_from_bytes(bytes) {
+        Ok(x) => x.to_string(),
+        Err(_) => bytes.escape_ascii().to_string(),
+    }
+}
+
+fn str_from_bytes(bytes: &[u8]) -> Result<&str, FfiStringError> {
+    str::from_utf8(bytes).map_err(|x| FfiStringError::InvalidUtf8 {
+        bytes: bytes.to_vec(),
+        valid_up_to: x.valid_up_to(),
+    })
+}
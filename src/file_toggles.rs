@@ -0,0 +1,82 @@
+//! Leaving single files out of dispatch, for bisecting which script of a large mod causes a regression
+//!
+//! # Example
+//! ```rs
+//! let suspect = FileId::new("weapons", "shotgun-Gun.grug");
+//! grug.set_file_enabled(&suspect, false)?;
+//! ```
+
+use std::{
+    collections::HashSet,
+    sync::{LazyLock, Mutex},
+};
+
+use crate::{Grug, GrugError};
+
+static DISABLED_FILES: LazyLock<Mutex<HashSet<FileId>>> =
+    LazyLock::new(|| Mutex::new(HashSet::new()));
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FileId {
+    pub mod_name: String,
+    /// Relative to the mod folder, like `FileSnapshot::path`
+    pub path: String,
+}
+
+impl FileId {
+    pub fn new<S1: ToString, S2: ToString>(mod_name: S1, path: S2) -> Self {
+        Self {
+            mod_name: mod_name.to_string(),
+            path: path.to_string(),
+        }
+    }
+}
+
+impl Grug {
+    /// Makes `activate_on_function` and `broadcast_all_entities` skip `file` while it's disabled,
+    /// leaving the other files of its mod running
+    pub fn set_file_enabled(&self, file: &FileId, enabled: bool) -> Result<(), GrugError> {
+        if !self
+            .source_mods_folder()
+            .join(&file.mod_name)
+            .join(&file.path)
+            .is_file()
+        {
+            return Err(GrugError::NotAModFile {
+                mod_name: file.mod_name.clone(),
+                path: file.path.clone(),
+            });
+        }
+
+        let mut disabled_files = DISABLED_FILES.lock().unwrap();
+        if enabled {
+            disabled_files.remove(file);
+        } else {
+            disabled_files.insert(file.clone());
+        }
+
+        Ok(())
+    }
+
+    pub fn is_file_enabled(&self, file: &FileId) -> bool {
+        !DISABLED_FILES.lock().unwrap().contains(file)
+    }
+
+    pub fn disabled_files(&self) -> Vec<FileId> {
+        DISABLED_FILES.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub fn enable_all_files(&self) {
+        DISABLED_FILES.lock().unwrap().clear();
+    }
+}
+
+/// Called before every on_function runs
+pub(crate) fn is_disabled(mod_name: &[u8], file_name: &[u8]) -> bool {
+    let disabled_files = DISABLED_FILES.lock().unwrap();
+
+    // Compared as bytes, so dispatch doesn't allocate
+    disabled_files
+        .iter()
+        .any(|x| x.mod_name.as_bytes() == mod_name && x.path.as_bytes() == file_name)
+}
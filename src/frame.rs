@@ -0,0 +1,86 @@
+//! Memory that lives until the end of the frame, for strings game functions hand to scripts
+//!
+//! grug only gets a pointer to the string a game function returns, so the string has to outlive the call.
+//! `#[game_function]`s returning a `String` get it copied in here, and `Grug::end_frame` frees everything at once.
+//! The chunks are kept around and reused, so a game that returns about as many strings every frame
+//! stops allocating after the first few frames.
+//!
+//! # Example
+//! ```rs
+//! #[game_function]
+//! fn player_name(id: u64) -> String {
+//!     PLAYERS.lock().unwrap()[&id].name.clone()
+//! }
+//!
+//! loop {
+//!     grug.activate_on_function("World", "on_update", &mut Arguments::empty())?;
+//!     grug.end_frame();
+//! }
+//! ```
+
+use std::{ffi::c_char, sync::Mutex};
+
+use crate::Grug;
+
+const CHUNK_SIZE: usize = 4096;
+
+struct FrameArena {
+    /// Boxed, so pointers into them stay valid when more chunks get added
+    chunks: Vec<Box<[u8]>>,
+    /// The chunk that's being allocated from
+    current: usize,
+    /// How much of the current chunk is taken
+    used: usize,
+}
+
+static ARENA: Mutex<FrameArena> = Mutex::new(FrameArena {
+    chunks: vec![],
+    current: 0,
+    used: 0,
+});
+
+impl Grug {
+    /// Frees every string game functions returned this frame
+    ///
+    /// Scripts can't hold on to strings past the on_function that got them,
+    /// so this is safe to call once no on_function is running.
+    pub fn end_frame(&self) {
+        let mut arena = ARENA.lock().unwrap();
+        arena.current = 0;
+        arena.used = 0;
+    }
+
+    /// How many bytes the frame arena holds on to, including what's free
+    pub fn frame_arena_capacity(&self) -> usize {
+        ARENA.lock().unwrap().chunks.iter().map(|x| x.len()).sum()
+    }
+}
+
+/// Copies `value` into the frame arena as a C string, which stays valid until `Grug::end_frame`
+///
+/// Used by `#[game_function]`s that return a `String`.
+pub fn alloc_c_str(value: &str) -> *const c_char {
+    let size = value.len() + 1;
+
+    let mut arena = ARENA.lock().unwrap();
+    let FrameArena {
+        chunks,
+        current,
+        used,
+    } = &mut *arena;
+
+    while *current < chunks.len() && chunks[*current].len() - *used < size {
+        *current += 1;
+        *used = 0;
+    }
+    if *current == chunks.len() {
+        chunks.push(vec![0; size.max(CHUNK_SIZE)].into_boxed_slice());
+    }
+
+    let bytes = &mut chunks[*current][*used..*used + size];
+    bytes[..value.len()].copy_from_slice(value.as_bytes());
+    bytes[value.len()] = 0;
+    *used += size;
+
+    bytes.as_ptr() as *const c_char
+}
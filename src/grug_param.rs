@@ -0,0 +1,108 @@
+//! How `#[game_function]` turns what grug passes in into parameters
+//!
+//! Every parameter type except `String` and references goes through `GrugParam`,
+//! so handle and newtype parameters only need an impl.
+
+use std::{
+    any::{Any, TypeId, type_name},
+    ffi::c_void,
+};
+
+use crate::grug_value::custom_type_of;
+
+/// A type `#[game_function]` can take as a parameter
+///
+/// # Example
+/// ```rs
+/// struct EntityHandle(u64);
+///
+/// impl GrugParam for EntityHandle {
+///     type Raw = u64;
+///     const GRUG_TYPE: &'static str = "id";
+///
+///     unsafe fn from_grug(raw: u64) -> Self {
+///         Self(raw)
+///     }
+/// }
+///
+/// #[game_function]
+/// fn despawn(entity: EntityHandle) {
+///     world.despawn(entity);
+/// }
+/// ```
+pub trait GrugParam: Sized {
+    /// What grug actually passes, one of `i32`, `f32`, `bool`, `u64` or a pointer for ids, or `*const c_char` for strings
+    type Raw;
+    /// The grug type `Raw` corresponds to, used by `Grug::check_game_functions`.
+    /// Every custom type is an `id`.
+    const GRUG_TYPE: &'static str;
+
+    /// Checks what a script passed before `from_grug` gets called.
+    /// An error gets reported to the script as a runtime error, and the game function returns right away.
+    fn validate(_raw: &Self::Raw) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// # Safety
+    /// `raw` has to be what grug passed for a parameter of type `GRUG_TYPE`, and has to have passed `validate`
+    unsafe fn from_grug(raw: Self::Raw) -> Self;
+}
+
+macro_rules! identity_param {
+    ($($type_:ty => $grug_type:literal),*) => {
+        $(
+            impl GrugParam for $type_ {
+                type Raw = Self;
+                const GRUG_TYPE: &'static str = $grug_type;
+
+                unsafe fn from_grug(raw: Self) -> Self {
+                    raw
+                }
+            }
+        )*
+    };
+}
+
+identity_param!(i32 => "i32", f32 => "f32", bool => "bool", u64 => "id");
+
+/// A `#[repr(C)]` plain-old-data struct that gets passed around by value, implemented by `#[derive(GrugStruct)]`
+///
+/// grug has no structs, so scripts get an id of a copy owned by the `Arguments` it was passed in.
+/// Game functions taking the struct by value get their own copy of it.
+///
+/// # Example
+/// ```rs
+/// #[derive(GrugStruct, Clone, Copy)]
+/// #[repr(C)]
+/// struct Vec2 {
+///     x: f32,
+///     y: f32,
+/// }
+///
+/// grug.activate_on_function("Bullet", "on_hit", &mut Arguments::new(vec![position.into()]))?;
+///
+/// #[game_function]
+/// fn spawn_explosion(position: Vec2) {
+///     world.explode(position);
+/// }
+/// ```
+pub trait GrugStruct: Copy + Any {}
+
+impl<T: GrugStruct> GrugParam for T {
+    type Raw = *const T;
+    const GRUG_TYPE: &'static str = "id";
+
+    /// Scripts can pass any id, including null and ids of other types
+    fn validate(raw: &*const T) -> Result<(), String> {
+        if custom_type_of(*raw as *mut c_void) != Some(TypeId::of::<T>()) {
+            return Err(format!("is not a `{}`", type_name::<T>()));
+        }
+
+        Ok(())
+    }
+
+    unsafe fn from_grug(raw: *const T) -> Self {
+        // SAFETY: `validate` found a live `CustomValue` of type `T` at `raw`
+        unsafe { *raw }
+    }
+}
@@ -1,24 +1,139 @@
 use std::{
-    any::Any,
+    any::{Any, TypeId},
     collections::HashMap,
     ffi::{CString, c_void},
     marker::PhantomData,
+    sync::{LazyLock, Mutex},
 };
 
-use crate::OpaqueGrugType;
+use crate::{
+    OpaqueGrugType,
+    alloc_tracking::{AllocationKind, track, untrack},
+};
+
+/// Every live `CustomValue`, keyed by its address
+static CUSTOM_TYPES: LazyLock<Mutex<HashMap<usize, CustomEntry>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+struct CustomEntry {
+    type_id: TypeId,
+    /// How many game function arguments currently borrow it, `-1` when one borrows it mutably.
+    /// Only kept up to date with the `borrow-check` feature.
+    #[cfg_attr(
+        not(all(feature = "borrow-check", not(feature = "release-unchecked"))),
+        allow(dead_code)
+    )]
+    borrows: isize,
+}
+
+fn register<T: Any>(raw: *mut c_void) {
+    CUSTOM_TYPES.lock().unwrap().insert(
+        raw as usize,
+        CustomEntry {
+            type_id: TypeId::of::<T>(),
+            borrows: 0,
+        },
+    );
+}
+
+/// Looks up the type of the `CustomValue` at `raw`, if there is one
+pub(crate) fn custom_type_of(raw: *mut c_void) -> Option<TypeId> {
+    CUSTOM_TYPES
+        .lock()
+        .unwrap()
+        .get(&(raw as usize))
+        .map(|x| x.type_id)
+}
+
+/// Borrow of a custom value by a game function argument, released when dropped
+#[doc(hidden)]
+pub struct CustomBorrow {
+    #[cfg_attr(
+        not(all(feature = "borrow-check", not(feature = "release-unchecked"))),
+        allow(dead_code)
+    )]
+    raw: usize,
+    #[cfg_attr(
+        not(all(feature = "borrow-check", not(feature = "release-unchecked"))),
+        allow(dead_code)
+    )]
+    mutable: bool,
+}
+
+/// Called by `#[game_function]` for every reference argument.
+///
+/// With the `borrow-check` feature, a script passing the same custom value to a `&mut` argument
+/// and to any other argument gets a runtime error instead, and `None` is returned.
+/// Without it, or with the `release-unchecked` feature, this never fails.
+#[doc(hidden)]
+pub fn borrow_custom(raw: *mut c_void, mutable: bool, argument: &str) -> Option<CustomBorrow> {
+    #[cfg(all(feature = "borrow-check", not(feature = "release-unchecked")))]
+    if let Some(entry) = CUSTOM_TYPES.lock().unwrap().get_mut(&(raw as usize)) {
+        if entry.borrows < 0 || (mutable && entry.borrows > 0) {
+            crate::game_function_error(&format!(
+                "`{argument}` is already passed to another argument of this game function"
+            ));
+            return None;
+        }
+
+        entry.borrows = if mutable { -1 } else { entry.borrows + 1 };
+    }
+    #[cfg(not(all(feature = "borrow-check", not(feature = "release-unchecked"))))]
+    let _ = argument;
+
+    Some(CustomBorrow {
+        raw: raw as usize,
+        mutable,
+    })
+}
+
+#[cfg(all(feature = "borrow-check", not(feature = "release-unchecked")))]
+impl Drop for CustomBorrow {
+    fn drop(&mut self) {
+        // The value can't be dropped during the game function, but untracked values have no entry
+        if let Some(entry) = CUSTOM_TYPES.lock().unwrap().get_mut(&self.raw) {
+            entry.borrows = if self.mutable { 0 } else { entry.borrows - 1 };
+        }
+    }
+}
 
 pub struct CustomValue<'a> {
-    raw: *mut c_void,
+    pub(crate) raw: *mut c_void,
+    /// Keeps the value alive when it's owned by this instead of borrowed
+    _owned: Option<Box<dyn Any>>,
     _marker: PhantomData<&'a mut ()>,
 }
 
 impl<'a> CustomValue<'a> {
     pub fn new<T: Any + 'static>(value: &'a mut T) -> Self {
+        let raw = value as *mut T as *mut c_void;
+        register::<T>(raw);
+
         Self {
-            raw: value as *mut T as *mut c_void,
+            raw,
+            _owned: None,
             _marker: PhantomData,
         }
     }
+
+    /// Same as `new`, but the value gets moved in, so scripts get a copy of it
+    pub fn owned<T: Any + 'static>(value: T) -> CustomValue<'static> {
+        let mut owned = Box::new(value);
+        let raw = &mut *owned as *mut T as *mut c_void;
+        register::<T>(raw);
+
+        CustomValue {
+            raw,
+            _owned: Some(owned),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl Drop for CustomValue<'_> {
+    fn drop(&mut self) {
+        CUSTOM_TYPES.lock().unwrap().remove(&(self.raw as usize));
+    }
 }
 
 pub enum GrugValue<'a> {
@@ -75,28 +190,55 @@ impl<'a> Arguments<'a> {
         }
     }
 
+    /// Swaps in new values, keeping the buffers they get marshalled into
+    ///
+    /// C strings that are still passed are kept as well, the rest get freed.
+    pub fn set_values(&mut self, values: Vec<GrugValue<'a>>) {
+        self.values = values;
+
+        let values = &self.values;
+        self.stored_c_strings.retain(|string, c_string| {
+            let passed = values
+                .iter()
+                .any(|x| matches!(x, GrugValue::String(x) if x == string));
+            if !passed {
+                untrack(c_string.as_ptr() as usize);
+            }
+            passed
+        });
+    }
+
     pub fn into_raw(&mut self) -> *mut *mut c_void {
-        let mut opaque_values = Vec::with_capacity(self.values.len());
+        // Reused, so calling this again for every dispatch doesn't allocate
+        let mut opaque_values = self.opaque_values.take().unwrap_or_default();
+        opaque_values.clear();
 
         for v in self.values.iter_mut() {
-            let raw_ptr = match v {
+            let opaque_value = match v {
                 GrugValue::String(v) => {
-                    let c_string = self
-                        .stored_c_strings
-                        .entry(v.clone())
-                        .or_insert_with(|| CString::new(v.as_str()).unwrap());
-                    c_string.as_ptr() as *mut c_void
+                    let c_string = self.stored_c_strings.entry(v.clone()).or_insert_with(|| {
+                        let c_string = CString::new(v.as_str()).unwrap();
+                        track(
+                            c_string.as_ptr() as usize,
+                            AllocationKind::String,
+                            || "on_function arguments".to_string(),
+                            c_string.as_bytes_with_nul().len(),
+                        );
+                        c_string
+                    });
+                    OpaqueGrugType::from_c_str(c_string)
                 }
-                GrugValue::I32(v) => v as *mut i32 as *mut c_void,
-                GrugValue::F32(v) => v as *mut f32 as *mut c_void,
-                GrugValue::Bool(v) => v as *mut bool as *mut c_void,
-                GrugValue::Custom(v) => v.raw,
+                GrugValue::I32(v) => OpaqueGrugType::from_i32(*v),
+                GrugValue::F32(v) => OpaqueGrugType::from_f32_ref(v),
+                GrugValue::Bool(v) => OpaqueGrugType::from_bool(*v),
+                GrugValue::Custom(v) => OpaqueGrugType::from_custom(v),
             };
 
-            opaque_values.push(OpaqueGrugType { raw: raw_ptr });
+            opaque_values.push(opaque_value);
         }
 
-        let mut raw_values = Vec::with_capacity(opaque_values.len());
+        let mut raw_values = self.raw_values.take().unwrap_or_default();
+        raw_values.clear();
         for value in opaque_values.iter_mut() {
             raw_values.push(value as *mut OpaqueGrugType as *mut c_void);
         }
@@ -107,3 +249,11 @@ impl<'a> Arguments<'a> {
         self.raw_values.as_mut().unwrap().as_mut_ptr()
     }
 }
+
+impl Drop for Arguments<'_> {
+    fn drop(&mut self) {
+        for c_string in self.stored_c_strings.values() {
+            untrack(c_string.as_ptr() as usize);
+        }
+    }
+}
@@ -0,0 +1,18 @@
+//! Hashing that stays the same across Rust versions, for anything that gets written to disk
+
+use std::{fs::read, path::Path};
+
+/// FNV-1a, since std's hasher isn't guaranteed to give the same result across Rust versions
+pub(crate) fn hash_bytes(bytes: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    format!("{hash:016x}")
+}
+
+pub(crate) fn hash_file(path: &Path) -> Option<String> {
+    read(path).ok().map(|x| hash_bytes(&x))
+}
@@ -0,0 +1,156 @@
+//! Running scripts on dedicated servers, where nobody reads stdout and mods only change when an admin says so
+//!
+//! See `examples/headless` for a server loop that uses all of it.
+//!
+//! # Example
+//! ```rs
+//! let grug = Grug::new(Some(silent_runtime_error_handler), ...)?;
+//! grug.set_auto_regenerate(false);
+//! grug.set_quarantine_policy(Some(QuarantinePolicy { max_runtime_errors: 100 }));
+//! grug.reload_on_sighup();
+//!
+//! loop {
+//!     if grug.take_reload_request() {
+//!         grug.regenerate_modified_mods()?;
+//!     }
+//!     grug.activate_on_function("World", "on_update", &mut Arguments::empty())?;
+//! }
+//! ```
+
+use std::{
+    collections::HashMap,
+    ffi::c_char,
+    sync::{
+        LazyLock, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use grug_sys::{grug_mods, grug_runtime_error_type};
+
+use crate::{Grug, file_toggles::FileId};
+
+static AUTO_REGENERATE: AtomicBool = AtomicBool::new(true);
+static RELOAD_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Disables files that keep erroring, instead of letting them spam runtime errors forever
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuarantinePolicy {
+    /// How many runtime errors a file can have before it gets disabled
+    pub max_runtime_errors: u64,
+}
+
+#[derive(Default)]
+struct Quarantine {
+    policy: Option<QuarantinePolicy>,
+    /// Runtime errors of every file since it was last released
+    errors: HashMap<FileId, u64>,
+    quarantined: Vec<FileId>,
+}
+
+static QUARANTINE: LazyLock<Mutex<Quarantine>> = LazyLock::new(Default::default);
+
+/// Runtime error handler that doesn't print anything
+///
+/// Runtime errors still show up in `Grug::snapshot`, which is where servers should report them from.
+///
+/// # Safety
+/// Only meant to be called by grug
+pub unsafe extern "C" fn silent_runtime_error_handler(
+    _reason: *const c_char,
+    _type_: grug_runtime_error_type,
+    _on_fn_name: *const c_char,
+    _on_fn_path: *const c_char,
+) {
+}
+
+impl Grug {
+    /// Whether `activate_on_function` and friends regenerate modified mods before running, which they do by default
+    ///
+    /// Turned off, changed mod files only get picked up by calling `regenerate_modified_mods`,
+    /// so a server only reloads mods when it's told to. The first dispatch still loads the mods.
+    pub fn set_auto_regenerate(&self, enabled: bool) {
+        AUTO_REGENERATE.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Applies `policy` from now on, or stops quarantining files when it's `None`
+    ///
+    /// Already quarantined files stay disabled.
+    pub fn set_quarantine_policy(&self, policy: Option<QuarantinePolicy>) {
+        QUARANTINE.lock().unwrap().policy = policy;
+    }
+
+    /// Every file the quarantine policy disabled, in the order they were disabled in
+    pub fn quarantined_files(&self) -> Vec<FileId> {
+        QUARANTINE.lock().unwrap().quarantined.clone()
+    }
+
+    /// Enables every quarantined file again, and forgets their runtime errors
+    pub fn release_quarantined_files(&self) {
+        let mut quarantine = QUARANTINE.lock().unwrap();
+        for file in quarantine.quarantined.drain(..) {
+            // It existed when it got quarantined, and removing it can't fail
+            let _ = self.set_file_enabled(&file, true);
+        }
+        quarantine.errors.clear();
+    }
+
+    /// Makes `SIGHUP` request a reload, which `take_reload_request` picks up
+    #[cfg(unix)]
+    pub fn reload_on_sighup(&self) {
+        unsafe extern "C" {
+            fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+        }
+
+        const SIGHUP: i32 = 1;
+
+        extern "C" fn request_reload(_: i32) {
+            // Storing to an atomic is about all a signal handler is allowed to do
+            RELOAD_REQUESTED.store(true, Ordering::Relaxed);
+        }
+
+        unsafe { signal(SIGHUP, request_reload) };
+    }
+
+    /// Requests a reload, like `SIGHUP` does after `reload_on_sighup`
+    pub fn request_reload(&self) {
+        RELOAD_REQUESTED.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether a reload was requested since the last call
+    pub fn take_reload_request(&self) -> bool {
+        RELOAD_REQUESTED.swap(false, Ordering::Relaxed)
+    }
+}
+
+/// Whether modified mods should get regenerated before dispatching
+pub(crate) fn should_regenerate() -> bool {
+    // SAFETY: This implements the copy trait so it's safe to use
+    #[allow(static_mut_refs)]
+    let mods = unsafe { grug_mods };
+
+    // Nothing can run before the mods got loaded once
+    AUTO_REGENERATE.load(Ordering::Relaxed) || mods.dirs.is_null()
+}
+
+/// Called after an on_function of a file had a runtime error
+pub(crate) fn note_runtime_error(grug: &Grug, mod_name: &[u8], file_name: &[u8]) {
+    let mut quarantine = QUARANTINE.lock().unwrap();
+    let Some(policy) = quarantine.policy else {
+        return;
+    };
+
+    let file = FileId::new(
+        String::from_utf8_lossy(mod_name),
+        String::from_utf8_lossy(file_name),
+    );
+    let errors = quarantine.errors.entry(file.clone()).or_default();
+    *errors += 1;
+
+    if *errors >= policy.max_runtime_errors && !quarantine.quarantined.contains(&file) {
+        // A file that got removed since it ran doesn't need disabling
+        if grug.set_file_enabled(&file, false).is_ok() {
+            quarantine.quarantined.push(file);
+        }
+    }
+}
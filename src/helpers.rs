@@ -0,0 +1,373 @@
+//! Calling helper functions of a file directly, for entry points that aren't tied to an engine event
+//!
+//! Like a `helper_describe()` that the game calls whenever it draws the tooltip of an entity.
+//! grug refuses to compile helper functions that no on_function calls,
+//! so the mod has to call it from one of its on_functions as well.
+//!
+//! Every call gets freshly initialized globals, just like on_functions do,
+//! and is checked against the signature of the helper function in the file before anything runs.
+//!
+//! # Example
+//! ```rs
+//! let file = FileId::new("weapons", "shotgun-Gun.grug");
+//!
+//! if grug.helper_functions(&file)?.iter().any(|x| x.name == "helper_describe") {
+//!     let tooltip: String = grug.call_helper_function(&file, "helper_describe", &mut Arguments::empty())?;
+//! }
+//! ```
+
+use std::{
+    collections::HashMap,
+    ffi::{CString, c_char, c_void},
+    fs::read_to_string,
+    path::{Path, PathBuf},
+    sync::{LazyLock, Mutex},
+    time::SystemTime,
+};
+
+use grug_sys::{grug_dump_file_to_json, grug_error, grug_fn_name, grug_fn_path};
+use seq_macro::seq;
+use serde::Deserialize;
+
+use crate::{
+    Arguments, Grug, GrugError, GrugFile, GrugParam, GrugValue, OpaqueGrugType,
+    ffi_string::{bytes_from_ptr, display_array},
+    file_toggles::FileId,
+    headless,
+    mod_api_type::Argument,
+    snapshot, stdlib,
+};
+
+/// How far below the stack pointer scripts may go, the same as grug's `GRUG_STACK_LIMIT`
+const STACK_LIMIT: u64 = 0x10000;
+
+/// The most arguments `call_helper_function` has calls generated for
+const MAX_HELPER_ARGUMENTS: usize = 4;
+
+/// Lives in the dll folder, and holds what grug last dumped of a file
+const AST_NAME: &str = "helper_functions.json";
+
+unsafe extern "C" {
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+
+    // Not in grug.h, since they're only meant for mods
+    static mut grug_has_runtime_error_happened: bool;
+    fn grug_get_max_rsp_addr() -> *mut u64;
+    fn grug_set_time_limit();
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HelperFunction {
+    pub name: String,
+    #[serde(default)]
+    pub arguments: Vec<Argument>,
+    /// `None` when it doesn't return anything
+    #[serde(default)]
+    pub return_type: Option<String>,
+}
+
+impl HelperFunction {
+    /// Like `(i32, string) -> bool`
+    fn signature(&self) -> String {
+        let arguments: Vec<_> = self.arguments.iter().map(|x| x.type_.as_str()).collect();
+        signature(&arguments, self.return_type.as_deref())
+    }
+}
+
+fn signature(arguments: &[&str], return_type: Option<&str>) -> String {
+    let arguments = arguments.join(", ");
+    match return_type {
+        Some(return_type) => format!("({arguments}) -> {return_type}"),
+        None => format!("({arguments})"),
+    }
+}
+
+/// What `call_helper_function` can return
+pub trait HelperReturn: Sized {
+    /// What the helper function actually returns
+    type Raw;
+    /// `None` for helper functions that don't return anything
+    const GRUG_TYPE: Option<&'static str>;
+
+    /// # Safety
+    /// `raw` has to be what a helper function returning `GRUG_TYPE` returned
+    unsafe fn from_raw(raw: Self::Raw) -> Self;
+}
+
+impl<T: GrugParam> HelperReturn for T {
+    type Raw = T::Raw;
+    const GRUG_TYPE: Option<&'static str> = Some(T::GRUG_TYPE);
+
+    unsafe fn from_raw(raw: T::Raw) -> Self {
+        unsafe { T::from_grug(raw) }
+    }
+}
+
+impl HelperReturn for () {
+    type Raw = ();
+    const GRUG_TYPE: Option<&'static str> = None;
+
+    unsafe fn from_raw(_: ()) -> Self {}
+}
+
+impl HelperReturn for String {
+    type Raw = *const c_char;
+    const GRUG_TYPE: Option<&'static str> = Some("string");
+
+    unsafe fn from_raw(raw: *const c_char) -> Self {
+        // Copied right away, since it can point into the frame arena or the dll
+        String::from_utf8_lossy(unsafe { bytes_from_ptr(raw) }.unwrap_or_default()).to_string()
+    }
+}
+
+/// The helper functions of a file, along with its modification time when they were read
+type CachedHelperFunctions = (SystemTime, Vec<HelperFunction>);
+
+/// The helper functions of every file that got read, keyed by source path
+static HELPER_FUNCTIONS: LazyLock<Mutex<HashMap<PathBuf, CachedHelperFunctions>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+impl Grug {
+    /// Every helper function `file` defines, read from its source
+    pub fn helper_functions(&self, file: &FileId) -> Result<Vec<HelperFunction>, GrugError> {
+        let path = self
+            .source_mods_folder()
+            .join(&file.mod_name)
+            .join(&file.path);
+        let modified =
+            path.metadata()
+                .and_then(|x| x.modified())
+                .map_err(|_| GrugError::NotAModFile {
+                    mod_name: file.mod_name.clone(),
+                    path: file.path.clone(),
+                })?;
+
+        let mut cache = HELPER_FUNCTIONS.lock().unwrap();
+        if let Some((cached_modified, helper_functions)) = cache.get(&path)
+            && *cached_modified == modified
+        {
+            return Ok(helper_functions.clone());
+        }
+
+        let helper_functions = self.read_helper_functions(&path)?;
+        cache.insert(path, (modified, helper_functions.clone()));
+
+        Ok(helper_functions)
+    }
+
+    /// Calls the helper function `name` of `file`, and returns what it returned
+    ///
+    /// `R` and `arguments` have to match the signature of the helper function.
+    /// Runtime errors get reported to the runtime error handler as usual, and are returned as `HelperRuntimeError`.
+    /// Not meant to be called from inside game functions.
+    ///
+    /// # Safety
+    /// Undefined behavior if custom arguments aren't what the helper function expects
+    pub fn call_helper_function<R: HelperReturn, S: ToString>(
+        &self,
+        file: &FileId,
+        name: S,
+        arguments: &mut Arguments,
+    ) -> Result<R, GrugError> {
+        let name = name.to_string();
+
+        let helper_function = self
+            .helper_functions(file)?
+            .into_iter()
+            .find(|x| x.name == name)
+            .ok_or_else(|| GrugError::NotAHelperFunction {
+                function_name: name.clone(),
+                path: file.path.clone(),
+            })?;
+        check_signature::<R>(&helper_function, arguments)?;
+
+        if headless::should_regenerate() {
+            self.regenerate_modified_mods()?;
+        }
+        let grug_file = self
+            .get_files_and_mods()
+            .into_iter()
+            .find(|(mod_name, x)| {
+                *mod_name == file.mod_name.as_bytes()
+                    && unsafe { bytes_from_ptr(x.inner.name) } == Some(file.path.as_bytes())
+            })
+            .map(|(_, x)| x)
+            .ok_or_else(|| GrugError::NotAModFile {
+                mod_name: file.mod_name.clone(),
+                path: file.path.clone(),
+            })?;
+
+        let grug_path = self.mods_folder.join(&file.mod_name).join(&file.path);
+        let _current_mod = stdlib::enter_mod(file.mod_name.as_bytes());
+        unsafe { self.run_helper_function(&grug_file, &name, &grug_path, arguments) }
+    }
+
+    /// Has grug dump the file at `path` to json, and picks the helper functions out of it
+    fn read_helper_functions(&self, path: &Path) -> Result<Vec<HelperFunction>, GrugError> {
+        let ast_path = self.mods_dll_folder.join(AST_NAME);
+
+        let input = CString::new(path.to_string_lossy().as_bytes()).unwrap_or_default();
+        let output = CString::new(ast_path.to_string_lossy().as_bytes()).unwrap_or_default();
+        if unsafe { grug_dump_file_to_json(input.as_ptr(), output.as_ptr()) } {
+            let error = unsafe { grug_error }; // SAFETY: This implements the copy trait so it's safe to use
+            return Err(GrugError::FileLoading {
+                name: display_array(&error.msg),
+                path: path.to_string_lossy().to_string(),
+            });
+        }
+
+        let json = read_to_string(&ast_path).map_err(|x| GrugError::ReadModFile {
+            path: ast_path.clone(),
+            error: x.to_string(),
+        })?;
+        let globals: Vec<serde_json::Value> =
+            serde_json::from_str(&json).map_err(|x| GrugError::Deserialize {
+                path: ast_path.clone(),
+                error: x.to_string(),
+            })?;
+
+        globals
+            .into_iter()
+            .filter(|x| x["type"] == "GLOBAL_HELPER_FN")
+            .map(|x| {
+                serde_json::from_value(x).map_err(|x| GrugError::Deserialize {
+                    path: ast_path.clone(),
+                    error: x.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// Sets up what the on_functions calling it normally would, and calls it
+    ///
+    /// # Safety
+    /// `arguments` has to match the signature of the helper function
+    unsafe fn run_helper_function<R: HelperReturn>(
+        &self,
+        file: &GrugFile,
+        name: &str,
+        grug_path: &Path,
+        arguments: &mut Arguments,
+    ) -> Result<R, GrugError> {
+        let symbol = CString::new(format!("{name}_safe")).unwrap_or_default();
+        let func = unsafe { dlsym(file.inner.dll, symbol.as_ptr()) };
+        if func.is_null() {
+            return Err(GrugError::NotAHelperFunction {
+                function_name: name.to_string(),
+                path: grug_path.to_string_lossy().to_string(),
+            });
+        }
+
+        let fn_name = CString::new(name).unwrap_or_default();
+        let fn_path = CString::new(grug_path.to_string_lossy().as_bytes()).unwrap_or_default();
+
+        let runtime_errors = snapshot::runtime_error_count();
+        let (globals, layout) = unsafe { file.init_globals() };
+
+        let raw = unsafe {
+            grug_fn_name = fn_name.as_ptr();
+            grug_fn_path = fn_path.as_ptr();
+
+            // The helper function checks for stack overflows against this, so it's measured from here
+            let stack_pointer = &raw const fn_name as u64;
+            *grug_get_max_rsp_addr() = stack_pointer - STACK_LIMIT;
+            grug_set_time_limit();
+            grug_has_runtime_error_happened = false;
+
+            let args = std::slice::from_raw_parts(arguments.into_raw(), arguments.values.len());
+            let globals = globals as *mut c_void;
+            seq!(N in 1..5 {
+                match args.len() {
+                    0 => {
+                        let func: unsafe extern "C" fn(*mut c_void) -> R::Raw = std::mem::transmute(func);
+                        func(globals)
+                    }
+                    #(N => seq!(M in 0..N {{
+                        let func: unsafe extern "C" fn(*mut c_void, #(OpaqueGrugType,)*) -> R::Raw =
+                            std::mem::transmute(func);
+                        func(globals, #(*(args[M] as *mut _),)*)
+                    }}),)*
+                    _ => unreachable!("`check_signature` limits the arguments"),
+                }
+            })
+        };
+
+        let result = match snapshot::runtime_error_count() != runtime_errors {
+            true => Err(GrugError::HelperRuntimeError {
+                function_name: name.to_string(),
+                reason: snapshot::last_runtime_error().reason,
+            }),
+            false => Ok(unsafe { R::from_raw(raw) }),
+        };
+
+        unsafe { GrugFile::free_globals(globals, layout) };
+
+        result
+    }
+}
+
+/// Makes sure the call matches what the helper function takes and returns
+fn check_signature<R: HelperReturn>(
+    helper_function: &HelperFunction,
+    arguments: &Arguments,
+) -> Result<(), GrugError> {
+    let unsupported = |reason: String| {
+        Err(GrugError::UnsupportedHelperFunction {
+            function_name: helper_function.name.clone(),
+            reason,
+        })
+    };
+    if helper_function.arguments.len() > MAX_HELPER_ARGUMENTS {
+        return unsupported(format!(
+            "it takes more than {MAX_HELPER_ARGUMENTS} arguments"
+        ));
+    }
+    // Arguments get passed in integer registers, where grug doesn't look for floats
+    if helper_function.arguments.iter().any(|x| x.type_ == "f32") {
+        return unsupported("it takes an `f32`".to_string());
+    }
+
+    let expected: Vec<_> = helper_function
+        .arguments
+        .iter()
+        .map(|x| x.type_.as_str())
+        .collect();
+
+    let got: Vec<_> = arguments
+        .values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| match value {
+            GrugValue::String(_) => "string",
+            GrugValue::I32(_) => "i32",
+            GrugValue::F32(_) => "f32",
+            GrugValue::Bool(_) => "bool",
+            // Custom values can be passed as any kind of id
+            GrugValue::Custom(_) => expected
+                .get(i)
+                .copied()
+                .filter(|x| !is_primitive(x))
+                .unwrap_or("id"),
+        })
+        .collect();
+
+    let return_matches = match (R::GRUG_TYPE, helper_function.return_type.as_deref()) {
+        (None, None) => true,
+        (Some(got), Some(expected)) => got == expected || (got == "id" && !is_primitive(expected)),
+        _ => false,
+    };
+
+    if got != expected || !return_matches {
+        return Err(GrugError::WrongHelperSignature {
+            function_name: helper_function.name.clone(),
+            expected: helper_function.signature(),
+            got: signature(&got, R::GRUG_TYPE),
+        });
+    }
+
+    Ok(())
+}
+
+fn is_primitive(type_: &str) -> bool {
+    matches!(type_, "string" | "i32" | "f32" | "bool")
+}
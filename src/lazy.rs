@@ -0,0 +1,298 @@
+//! Only compiling the files of entity types the game actually uses, and switching mods folders
+//!
+//! grug compiles every file in its mods folder, so in lazy mode it gets a shadow mods folder instead.
+//! That folder mirrors the real one, but only links in the grug files of entity types that have been requested.
+//!
+//! grug can't be pointed at another mods folder once it's initialized,
+//! so switching mods folders also goes through a shadow mods folder, which then links in every file.
+
+#[cfg(unix)]
+use std::os::unix::fs::symlink;
+// Only files get linked, so file links are enough
+#[cfg(windows)]
+use std::os::windows::fs::symlink_file as symlink;
+use std::{
+    collections::HashSet,
+    fs::{create_dir_all, read_dir, remove_dir_all, remove_file, symlink_metadata},
+    io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use crate::{ErrorHandler, Grug, GrugError, abi::remove_dlls, profiles::Profile};
+
+/// Name of the shadow mods folder inside the dll folder
+pub const LAZY_MODS_FOLDER: &str = "lazy_mods";
+
+pub(crate) struct LazyMods {
+    /// The real mods folder
+    source_folder: Mutex<PathBuf>,
+    entity_types: Mutex<HashSet<String>>,
+    /// Whether every file gets linked in, instead of only the ones of `entity_types`
+    all_entity_types: bool,
+    /// The mods that get linked in, in load order, or every mod if there's no profile
+    pub(crate) profile: Mutex<Option<Profile>>,
+}
+
+impl Grug {
+    /// Same as `Grug::new`, but files are only compiled once their entity type is needed.
+    ///
+    /// The first `activate_on_function` of an entity type compiles its files,
+    /// or `preload_entity` can be used to do that up front, behind a loading screen.
+    ///
+    /// Files that refer to entities of another type fail to load until that type is loaded too.
+    ///
+    /// # Example
+    /// ```rs
+    /// let grug = Grug::new_lazy(
+    ///     None,
+    ///     "./examples/mod_api.json",
+    ///     "./examples/mods",
+    ///     "./examples/mods_dll",
+    ///     1000,
+    /// )?;
+    /// grug.preload_entity("Boss")?;
+    /// ```
+    pub fn new_lazy<P1, P2, P3>(
+        error_handler: Option<ErrorHandler>,
+        mod_api_path: P1,
+        mods_folder: P2,
+        mods_dll_folder: P3,
+        timeout_ms: u64,
+    ) -> Result<Self, GrugError>
+    where
+        P1: Into<PathBuf>,
+        P2: Into<PathBuf>,
+        P3: Into<PathBuf>,
+    {
+        Self::new_shadowed(
+            error_handler,
+            mod_api_path,
+            mods_folder.into(),
+            mods_dll_folder.into(),
+            timeout_ms,
+            false,
+        )
+    }
+
+    /// Same as `Grug::new`, but the mods folder can be switched with `set_mods_folder` afterwards
+    ///
+    /// # Example
+    /// ```rs
+    /// let grug = Grug::new_switchable(
+    ///     None,
+    ///     "./examples/mod_api.json",
+    ///     "./profiles/default",
+    ///     "./examples/mods_dll",
+    ///     1000,
+    /// )?;
+    /// grug.set_mods_folder("./profiles/hardcore")?;
+    /// ```
+    pub fn new_switchable<P1, P2, P3>(
+        error_handler: Option<ErrorHandler>,
+        mod_api_path: P1,
+        mods_folder: P2,
+        mods_dll_folder: P3,
+        timeout_ms: u64,
+    ) -> Result<Self, GrugError>
+    where
+        P1: Into<PathBuf>,
+        P2: Into<PathBuf>,
+        P3: Into<PathBuf>,
+    {
+        Self::new_shadowed(
+            error_handler,
+            mod_api_path,
+            mods_folder.into(),
+            mods_dll_folder.into(),
+            timeout_ms,
+            true,
+        )
+    }
+
+    fn new_shadowed<P1: Into<PathBuf>>(
+        error_handler: Option<ErrorHandler>,
+        mod_api_path: P1,
+        source_folder: PathBuf,
+        mods_dll_folder: PathBuf,
+        timeout_ms: u64,
+        all_entity_types: bool,
+    ) -> Result<Self, GrugError> {
+        let shadow_folder = mods_dll_folder.join(LAZY_MODS_FOLDER);
+
+        let lazy = LazyMods {
+            source_folder: Mutex::new(source_folder),
+            entity_types: Mutex::new(HashSet::new()),
+            all_entity_types,
+            profile: Mutex::new(None),
+        };
+        lazy.sync(&shadow_folder)?;
+
+        let mut grug = Self::new(
+            error_handler,
+            mod_api_path,
+            shadow_folder,
+            mods_dll_folder,
+            timeout_ms,
+        )?;
+        grug.lazy = Some(lazy);
+
+        Ok(grug)
+    }
+
+    /// Compiles and loads the files of `entity_name`, if that hasn't happened yet.
+    ///
+    /// Does nothing if grug wasn't created with `Grug::new_lazy`, since everything is loaded then.
+    pub fn preload_entity<S: ToString>(&self, entity_name: S) -> Result<(), GrugError> {
+        let entity_name = entity_name.to_string();
+
+        if !self.entities.contains_key(&entity_name) {
+            return Err(GrugError::NotAnEntity { entity_name });
+        }
+
+        let Some(lazy) = &self.lazy else {
+            return Ok(());
+        };
+
+        if lazy.entity_types.lock().unwrap().insert(entity_name) {
+            self.regenerate_modified_mods()?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether the files of `entity_name` are loaded, which is always the case outside of lazy mode
+    pub fn is_entity_loaded<S: ToString>(&self, entity_name: S) -> bool {
+        match &self.lazy {
+            Some(lazy) => lazy.links_entity_type(&entity_name.to_string()),
+            None => true,
+        }
+    }
+
+    /// Switches to the mods in `mods_folder`, and regenerates the mods.
+    ///
+    /// Every dll gets thrown away, since they were compiled from the old mods.
+    /// Only works when grug was created with `Grug::new_switchable` or `Grug::new_lazy`.
+    pub fn set_mods_folder<P: Into<PathBuf>>(&self, mods_folder: P) -> Result<(), GrugError> {
+        let mods_folder: PathBuf = mods_folder.into();
+
+        let Some(lazy) = &self.lazy else {
+            return Err(GrugError::ModsFolderNotSwitchable);
+        };
+
+        if !mods_folder.is_dir() {
+            return Err(GrugError::NotAModsFolder { path: mods_folder });
+        }
+
+        *lazy.source_folder.lock().unwrap() = mods_folder;
+
+        // Mods with the same name in both folders would otherwise keep the links and dlls of the old one
+        remove_dir_all(&self.mods_folder).map_err(|x| GrugError::LazyMods {
+            path: self.mods_folder.clone(),
+            error: x.to_string(),
+        })?;
+        remove_dlls(&self.mods_dll_folder, &mut 0);
+
+        #[cfg(feature = "settings")]
+        crate::stdlib::settings::forget_loaded_settings();
+
+        self.regenerate_modified_mods()
+    }
+
+    /// The folder the mods are actually in, since grug sees the shadow mods folder in lazy mode
+    pub(crate) fn source_mods_folder(&self) -> PathBuf {
+        match &self.lazy {
+            Some(lazy) => lazy.source_folder.lock().unwrap().clone(),
+            None => self.mods_folder.clone(),
+        }
+    }
+
+    /// Brings the shadow mods folder up to date with the real one, in lazy mode
+    pub(crate) fn sync_lazy_mods(&self) -> Result<(), GrugError> {
+        match &self.lazy {
+            Some(lazy) => lazy.sync(&self.mods_folder),
+            None => Ok(()),
+        }
+    }
+}
+
+impl LazyMods {
+    fn links_entity_type(&self, entity_type: &str) -> bool {
+        self.all_entity_types || self.entity_types.lock().unwrap().contains(entity_type)
+    }
+
+    fn is_mod_enabled(&self, mod_name: &str) -> bool {
+        match &*self.profile.lock().unwrap() {
+            Some(profile) => profile.mods.iter().any(|x| x == mod_name),
+            None => true,
+        }
+    }
+
+    fn sync(&self, shadow_folder: &Path) -> Result<(), GrugError> {
+        let source_folder = self.source_folder.lock().unwrap().clone();
+        self.sync_dir(&source_folder, shadow_folder, 0)
+            .map_err(|x| GrugError::LazyMods {
+                path: shadow_folder.to_path_buf(),
+                error: x.to_string(),
+            })
+    }
+
+    /// `depth` is 0 for the mods folder itself, and 1 for the folder of a mod
+    fn sync_dir(&self, source_dir: &Path, shadow_dir: &Path, depth: usize) -> io::Result<()> {
+        create_dir_all(shadow_dir)?;
+
+        let mut wanted = HashSet::new();
+
+        for entry in read_dir(source_dir)?.flatten() {
+            let source_path = entry.path();
+            let name = entry.file_name();
+            let shadow_path = shadow_dir.join(&name);
+
+            let is_wanted = if depth == 0 && !self.is_mod_enabled(&name.to_string_lossy()) {
+                false
+            } else if source_path.is_dir() {
+                self.sync_dir(&source_path, &shadow_path, depth + 1)?;
+                true
+            } else if depth == 0 {
+                // grug ignores files outside of mods
+                false
+            } else if depth == 1 && name == "about.json" {
+                true
+            } else {
+                entity_type_of(&name.to_string_lossy()).is_some_and(|x| self.links_entity_type(x))
+            };
+
+            if !is_wanted {
+                continue;
+            }
+
+            if !source_path.is_dir() && symlink_metadata(&shadow_path).is_err() {
+                symlink(source_path.canonicalize()?, &shadow_path)?;
+            }
+            wanted.insert(name);
+        }
+
+        // grug fails on links to files that don't exist anymore, so they have to go
+        for entry in read_dir(shadow_dir)?.flatten() {
+            if wanted.contains(&entry.file_name()) {
+                continue;
+            }
+
+            if entry.file_type()?.is_dir() {
+                remove_dir_all(entry.path())?;
+            } else {
+                remove_file(entry.path())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Uses the same rule as grug: the entity type of `ak47-Gun.grug` is `Gun`
+fn entity_type_of(file_name: &str) -> Option<&str> {
+    let file_name = file_name.strip_suffix(".grug")?;
+    let (_, entity_type) = file_name.split_once('-')?;
+
+    entity_type.split('.').next()
+}
@@ -86,29 +86,93 @@
 //! }
 //! ```
 
+//! # Features
+//! - `compile` (default): compile mods one file at a time, for progress callbacks and cancellation
+//! - `cache`: keep a source hash manifest in the dll folder, so unchanged mods don't get recompiled
+//! - `abort`: the `should_abort` game function, for long running scripts to stop early when the player cancels
+//! - `commands`: the `emit_command` game function and `CommandBuffer`, for scripts to request engine actions
+//! - `constants`: the `get_constant_*` game functions, for handing engine constants to scripts
+//! - `events`: the `emit` game function and `Grug::subscribe`, for scripts to notify game systems
+//! - `random`: the `rand_*` game functions, with a seeded stream per instance so simulations can be replayed
+//! - `settings`: the `get_setting_*` game functions, for mods to declare settings players can change
+//! - `store`: the `store_set_*` and `store_get_*` game functions, for mods to keep progress between sessions
+//! - `borrow-check`: turn a script aliasing a `&mut` custom argument into a runtime error, instead of undefined behavior
+//! - `alloc-tracking`: track every buffer allocated for scripts, and panic on leaks when `Grug` gets dropped
+//! - `release-unchecked`: compile out argument validation, `borrow-check`, `alloc-tracking` and profiling hooks,
+//!   leaving only the call itself
+
 pub use grug_sys;
 
+pub mod abi;
+pub mod alloc_tracking;
+pub mod batch;
+pub mod broadcast;
+#[cfg(feature = "cache")]
+pub mod cache;
+pub mod capabilities;
+#[cfg(feature = "compile")]
+pub mod compile;
+pub mod crash_report;
+pub mod determinism;
+pub mod dispatch;
+pub mod dynamic;
+pub mod ffi_string;
+pub mod file_toggles;
+pub mod frame;
+pub mod grug_param;
 pub mod grug_value;
+mod hash;
+pub mod headless;
+pub mod helpers;
+pub mod lazy;
+pub mod memory;
+pub mod migrate;
 pub mod mod_api_type;
-mod to_string_wrapper;
+pub mod package;
+pub mod pause;
+pub mod perf_guard;
+pub mod profiles;
+pub mod registry;
+pub mod server_report;
+pub mod snapshot;
+pub mod stdlib;
+pub mod symbolize;
+pub mod table;
+#[cfg(feature = "test-utils")]
+pub mod test_isolation;
+pub mod update;
+mod walk;
 
 use std::{
-    alloc::{Layout, alloc},
+    alloc::{Layout, alloc, dealloc},
+    any::{Any, TypeId},
     collections::HashMap,
     ffi::{CStr, CString, OsString, c_char, c_void},
-    fs::read_to_string,
+    fs::{read_to_string, write},
     path::PathBuf,
-    ptr::null_mut,
     slice::{from_raw_parts, from_raw_parts_mut},
+    time::{Duration, Instant},
 };
 
 use grug_sys::*;
 use seq_macro::seq;
+use serde::Serialize;
 use serde_json::from_str;
 use thiserror::Error;
 
-pub use crate::grug_value::{Arguments, GrugValue};
-use crate::{mod_api_type::ModAPI, to_string_wrapper::ToStringWrapper};
+pub use crate::capabilities::{Capabilities, backend_version, capabilities};
+#[cfg(feature = "compile")]
+pub use crate::compile::{CancelToken, ProgressCallback};
+pub use crate::grug_param::{GrugParam, GrugStruct};
+pub use crate::grug_value::{Arguments, CustomValue, GrugValue};
+use crate::{
+    abi::AbiMismatch,
+    alloc_tracking::{AllocationKind, track, untrack},
+    dispatch::FileStatus,
+    ffi_string::{bytes_from_ptr, display_array, display_ptr},
+    lazy::LazyMods,
+    mod_api_type::{Entity, ModAPI},
+};
 
 /// Errors from Grug
 #[derive(Error, Debug)]
@@ -119,19 +183,110 @@ pub enum GrugError {
     ReadModAPI { path: PathBuf, error: String },
     #[error("Failed to deserialize `{path}`: `{error}`")]
     Deserialize { path: PathBuf, error: String },
+    #[error("Failed to write: `{path}`: `{error}`")]
+    WriteModAPI { path: PathBuf, error: String },
+    #[error("Failed to read: `{path}`: `{error}`")]
+    ReadModFile { path: PathBuf, error: String },
+    #[error("Failed to write: `{path}`: `{error}`")]
+    WriteModFile { path: PathBuf, error: String },
+    #[error("`{path}` is not a path inside of a mod")]
+    InvalidModPath { path: PathBuf },
+    #[error("`{mod_name}` is not an installed mod")]
+    NotAMod { mod_name: String },
+    #[error("`{path}` is not a file of `{mod_name}`")]
+    NotAModFile { mod_name: String, path: String },
+    #[error("`{mod_name}` has no update to roll back")]
+    NoModBackup { mod_name: String },
+    #[error("`{mod_name}` has no setting `{name}`")]
+    UnknownModSetting { mod_name: String, name: String },
+    #[error("Setting `{name}` is a `{expected}`, not a `{got}`")]
+    WrongModSettingType {
+        name: String,
+        expected: String,
+        got: String,
+    },
+    #[error("Setting `{name}` can't be `{value}`, only finite numbers")]
+    NonFiniteModSetting { name: String, value: f32 },
+    #[error(
+        "The mods folder can only be switched when grug was created with `Grug::new_switchable` or `Grug::new_lazy`"
+    )]
+    ModsFolderNotSwitchable,
+    #[error("`{path}` is not a mods folder")]
+    NotAModsFolder { path: PathBuf },
+    #[error("Failed to read: `{path}`: `{error}`")]
+    ReadProfiles { path: PathBuf, error: String },
+    #[error("Failed to write: `{path}`: `{error}`")]
+    WriteProfiles { path: PathBuf, error: String },
+    #[error("There is no profile named `{name}`")]
+    UnknownProfile { name: String },
+    #[error("Failed to write: `{path}`: `{error}`")]
+    WriteAbiMarker { path: PathBuf, error: String },
+    #[error("Failed to read: `{path}`: `{error}`")]
+    ReadPerfBaseline { path: PathBuf, error: String },
+    #[error("Failed to write: `{path}`: `{error}`")]
+    WritePerfBaseline { path: PathBuf, error: String },
+    #[error("The update of `{mod_name}` was rolled back, since it failed: `{error}`")]
+    UpdateRolledBack { mod_name: String, error: String },
+    #[error(
+        "The update of `{mod_name}` failed: `{error}`, and rolling it back failed too: `{rollback_error}`"
+    )]
+    UpdateRollbackFailed {
+        mod_name: String,
+        error: String,
+        rollback_error: String,
+    },
+    #[error("`{path}` already exists, or more than one file would be renamed to it")]
+    MigrationConflict { path: PathBuf },
     #[error("`{function_name}` is not a on_function")]
     NotAnOnFunction { function_name: String },
+    #[error(
+        "`{function_name}` can't be passed an `f32` yet, since grug takes them in float registers"
+    )]
+    UnsupportedF32Argument { function_name: String },
     #[error("`{entity_name}` is not an entity")]
     NotAnEntity { entity_name: String },
+    #[error("`{entity_name}` is already an entity")]
+    EntityAlreadyExists { entity_name: String },
+    #[error("`{entity_name}` is not a valid entity name, it has to be PascalCase")]
+    InvalidEntityName { entity_name: String },
     #[error("Grug failed to load: `{name}` in `{path}`")]
     FileLoading { name: String, path: String },
     #[error("Grug regenerating error: `{error}`")]
     Regenerating { error: String },
     #[error("Grug function not defined")]
     UndefinedFunction,
+    #[error("Failed to update the lazily loaded mods in `{path}`: `{error}`")]
+    LazyMods { path: PathBuf, error: String },
+    #[error("`{function_name}` is not a helper function of `{path}`")]
+    NotAHelperFunction { function_name: String, path: String },
+    #[error("`{function_name}` can't be called, since {reason}")]
+    UnsupportedHelperFunction {
+        function_name: String,
+        reason: String,
+    },
+    #[error("`{function_name}` is `{expected}`, not `{got}`")]
+    WrongHelperSignature {
+        function_name: String,
+        expected: String,
+        got: String,
+    },
+    #[error("`{function_name}` had a runtime error: `{reason}`")]
+    HelperRuntimeError {
+        function_name: String,
+        reason: String,
+    },
+    #[error("`{name}` is already registered as a game function")]
+    GameFunctionAlreadyRegistered { name: String },
+    #[cfg(feature = "compile")]
+    #[error("Compiling mods was cancelled with `{remaining}` files left")]
+    Cancelled {
+        compiled: Vec<PathBuf>,
+        remaining: usize,
+    },
 }
 
 #[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum GrugRuntimeError {
     DivisionByZero,
     StackOverflow,
@@ -140,6 +295,19 @@ pub enum GrugRuntimeError {
     GameFnError,
 }
 
+impl From<grug_runtime_error_type> for GrugRuntimeError {
+    #[allow(non_upper_case_globals)] // The names bindgen gives the constants
+    fn from(type_: grug_runtime_error_type) -> Self {
+        match type_ {
+            grug_runtime_error_type_GRUG_ON_FN_DIVISION_BY_ZERO => Self::DivisionByZero,
+            grug_runtime_error_type_GRUG_ON_FN_STACK_OVERFLOW => Self::StackOverflow,
+            grug_runtime_error_type_GRUG_ON_FN_TIME_LIMIT_EXCEEDED => Self::TimeLimitExceeded,
+            grug_runtime_error_type_GRUG_ON_FN_OVERFLOW => Self::Overflow,
+            _ => Self::GameFnError,
+        }
+    }
+}
+
 pub type ErrorHandler =
     unsafe extern "C" fn(*const c_char, grug_runtime_error_type, *const c_char, *const c_char);
 
@@ -154,23 +322,9 @@ pub unsafe extern "C" fn default_runtime_error_handler(
     on_fn_path: *const c_char,
 ) {
     // Convert inputs safely
-    let reason = if !reason.is_null() {
-        unsafe { CStr::from_ptr(reason).to_string_lossy() }
-    } else {
-        "<no reason>".into()
-    };
-
-    let fn_name = if !on_fn_name.is_null() {
-        unsafe { CStr::from_ptr(on_fn_name).to_string_lossy() }
-    } else {
-        "<unknown fn>".into()
-    };
-
-    let fn_path = if !on_fn_path.is_null() {
-        unsafe { CStr::from_ptr(on_fn_path).to_string_lossy() }
-    } else {
-        "<unknown path>".into()
-    };
+    let reason = unsafe { display_ptr(reason, "<no reason>") };
+    let fn_name = unsafe { display_ptr(on_fn_name, "<unknown fn>") };
+    let fn_path = unsafe { display_ptr(on_fn_path, "<unknown path>") };
 
     eprintln!(
         "Grug runtime error: {}\n  at {} ({})",
@@ -178,10 +332,24 @@ pub unsafe extern "C" fn default_runtime_error_handler(
     );
 }
 
+/// Makes grug return a runtime error from the current on_function, once the calling game function returns
+///
+/// Only meant to be called from inside game functions.
+pub fn game_function_error(message: &str) {
+    let message = CString::new(message).unwrap_or_default();
+    unsafe { grug_game_function_error_happened(message.as_ptr()) };
+}
+
 pub struct Grug {
-    #[allow(dead_code)]
-    mod_api: ModAPI, // Here just in case
+    mod_api: ModAPI,
+    mod_api_path: PathBuf,
     entities: HashMap<String, HashMap<String, usize>>,
+    mods_folder: PathBuf,
+    mods_dll_folder: PathBuf,
+    #[cfg(feature = "compile")]
+    progress_callback: Option<ProgressCallback>,
+    lazy: Option<LazyMods>,
+    abi_mismatch: Option<AbiMismatch>,
 }
 
 impl Grug {
@@ -232,10 +400,13 @@ impl Grug {
             error: x.to_string(),
         })?;
 
-        // Initialize grug
+        let abi_mismatch = abi::check_dll_folder(&mods_dll_folder)?;
+
+        // Initialize grug, with an error handler that records errors for snapshots before calling the real one
+        snapshot::set_error_handler(error_handler.unwrap_or(default_runtime_error_handler));
         let result = unsafe {
             grug_init(
-                Some(error_handler.unwrap_or(default_runtime_error_handler)),
+                Some(snapshot::record_runtime_error),
                 CString::new(mod_api_path.as_os_str().to_string_lossy().to_string())
                     .unwrap()
                     .as_ptr(),
@@ -252,32 +423,28 @@ impl Grug {
         let entities = mod_api
             .entities
             .iter()
-            .map(|(name, data)| {
-                let mut i = 0;
-                (
-                    name.clone(),
-                    data.on_functions
-                        .keys()
-                        .map(|k| {
-                            let return_val = (k.clone(), i);
-                            // println!("{k}");
-                            i += 1;
-                            return_val
-                        })
-                        .collect(),
-                )
-            })
+            .map(|(name, data)| (name.clone(), on_function_indices(data)))
             .collect();
 
         if result {
             #[allow(static_mut_refs)]
             let error = unsafe { grug_error }; // SAFETY: This implements the copy trait so it's safe to use
             return Err(GrugError::Init {
-                error: error.msg.to_string(),
+                error: display_array(&error.msg),
             });
         }
 
-        Ok(Self { mod_api, entities })
+        Ok(Self {
+            mod_api,
+            mod_api_path,
+            entities,
+            mods_folder,
+            mods_dll_folder,
+            #[cfg(feature = "compile")]
+            progress_callback: None,
+            lazy: None,
+            abi_mismatch,
+        })
     }
 
     /// # Safety
@@ -290,12 +457,12 @@ impl Grug {
             let error = unsafe { grug_error }; // SAFETY: This implements the copy trait so it's safe to use
             if unsafe { grug_loading_error_in_grug_file } {
                 return Err(GrugError::FileLoading {
-                    name: error.msg.to_string(),
-                    path: error.path.to_string(),
+                    name: display_array(&error.msg),
+                    path: display_array(&error.path),
                 });
             } else {
                 return Err(GrugError::Regenerating {
-                    error: error.msg.to_string(),
+                    error: display_array(&error.msg),
                 });
             }
         }
@@ -303,14 +470,76 @@ impl Grug {
         Ok(())
     }
 
+    /// Adds an entity type, for hosts that let players define their own.
+    ///
+    /// The entity type gets written to `mod_api.json`, and `activate_on_function` accepts it right away.
+    /// grug only reads `mod_api.json` during `Grug::new` though,
+    /// so files of the new entity type don't load, and nothing runs for it, until the game restarts.
+    ///
+    /// # Example
+    /// ```rs
+    /// grug.register_entity_type("TurretMk2", Entity {
+    ///     description: "A turret placed in the editor".to_string(),
+    ///     on_functions: LinkedHashMap::new(),
+    /// })?;
+    /// ```
+    pub fn register_entity_type<S: ToString>(
+        &mut self,
+        entity_name: S,
+        entity: Entity,
+    ) -> Result<(), GrugError> {
+        let entity_name = entity_name.to_string();
+
+        // Same rule grug uses, so the written mod_api.json still loads next time
+        let is_pascal_case = entity_name.starts_with(|x: char| x.is_ascii_uppercase())
+            && entity_name.chars().all(|x| x.is_ascii_alphanumeric());
+        if !is_pascal_case {
+            return Err(GrugError::InvalidEntityName { entity_name });
+        }
+
+        if self.mod_api.entities.contains_key(&entity_name) {
+            return Err(GrugError::EntityAlreadyExists { entity_name });
+        }
+
+        let indices = on_function_indices(&entity);
+        self.mod_api.entities.insert(entity_name.clone(), entity);
+
+        let mod_api_json = serde_json::to_string_pretty(&self.mod_api).unwrap();
+        if let Err(error) = write(&self.mod_api_path, mod_api_json) {
+            // Otherwise it'd be known here, but not to grug after a restart
+            self.mod_api.entities.remove(&entity_name);
+
+            return Err(GrugError::WriteModAPI {
+                path: self.mod_api_path.clone(),
+                error: error.to_string(),
+            });
+        }
+        self.entities.insert(entity_name, indices);
+
+        self.regenerate_modified_mods()
+    }
+
     /// Regenerates modified mods
+    ///
+    /// If a progress callback is set, files that haven't been loaded yet are compiled one at a time first,
+    /// so the callback gets called in between them.
     pub fn regenerate_modified_mods(&self) -> Result<(), GrugError> {
+        self.sync_lazy_mods()?;
+
+        #[cfg(feature = "compile")]
+        if self.progress_callback.is_some() {
+            self.compile_stale_files(&CancelToken::new())?;
+        }
+
         unsafe { Self::regenerate_modified_mods_unchecked() }
     }
 
     /// Activates an `on_function` on a given `entity`
     ///
-    /// Automatically calls `regenerate_modified_mods`
+    /// Automatically calls `regenerate_modified_mods` unless turned off with `set_auto_regenerate`,
+    /// and `preload_entity` when grug was created with `Grug::new_lazy`
+    ///
+    /// Does nothing while grug is paused
     ///
     /// # Example
     /// ```rs
@@ -325,9 +554,38 @@ impl Grug {
         on_function_name: S2,
         arguments: &mut Arguments,
     ) -> Result<(), GrugError> {
-        self.regenerate_modified_mods()?;
+        if self.is_paused() {
+            return Ok(());
+        }
+
+        let entity_name = entity_name.to_string();
+        let on_function_name = on_function_name.to_string();
+
+        let index = self.prepare_on_function(&entity_name, &on_function_name)?;
+
+        let files = self.get_files_and_mods_by_entity_type(entity_name);
+
+        for (mod_name, file) in files {
+            unsafe { self.run_file(mod_name, &file, index, &on_function_name, arguments)? };
+        }
+
+        Ok(())
+    }
+
+    /// Loads and regenerates the mods of `entity_name`, and returns the index of the on_function in their files
+    fn prepare_on_function(
+        &self,
+        entity_name: &str,
+        on_function_name: &str,
+    ) -> Result<usize, GrugError> {
+        if !self.is_entity_loaded(entity_name) {
+            self.preload_entity(entity_name)?;
+        }
+        if headless::should_regenerate() {
+            self.regenerate_modified_mods()?;
+        }
 
-        let on_functions = self.entities.get(&entity_name.to_string());
+        let on_functions = self.entities.get(entity_name);
 
         if on_functions.is_none() {
             return Err(GrugError::NotAnEntity {
@@ -335,7 +593,7 @@ impl Grug {
             });
         }
 
-        let index = on_functions.unwrap().get(&on_function_name.to_string());
+        let index = on_functions.unwrap().get(on_function_name);
 
         if index.is_none() {
             return Err(GrugError::NotAnOnFunction {
@@ -343,15 +601,73 @@ impl Grug {
             });
         }
 
-        let index = *index.unwrap();
+        Ok(*index.unwrap())
+    }
+
+    /// Runs an on_function of a file, timing it for snapshots and `perf_guard` unless `release-unchecked` is enabled.
+    /// Files that don't define the on_function, or that are disabled, are skipped.
+    ///
+    /// Returns what happened, along with how long it ran for
+    ///
+    /// # Safety
+    /// Undefined behavior if arguments passed in are incorrect
+    unsafe fn run_file(
+        &self,
+        mod_name: &[u8],
+        file: &GrugFile,
+        index: usize,
+        on_function_name: &str,
+        arguments: &mut Arguments,
+    ) -> Result<(FileStatus, Duration), GrugError> {
+        let file_name = unsafe { bytes_from_ptr(file.inner.name) }.unwrap_or_default();
+        if !unsafe { file.defines_on_function(index) } {
+            return Ok((FileStatus::Undefined, Duration::ZERO));
+        }
+        if file_toggles::is_disabled(mod_name, file_name) {
+            return Ok((FileStatus::Disabled, Duration::ZERO));
+        }
+        // The on_function would get a garbage value
+        if arguments
+            .values
+            .iter()
+            .any(|x| matches!(x, GrugValue::F32(_)))
+        {
+            return Err(GrugError::UnsupportedF32Argument {
+                function_name: on_function_name.to_string(),
+            });
+        }
 
-        let files = self.get_files_by_entity_type(entity_name);
+        // Constant, so these get compiled out with `release-unchecked`
+        let instrumented = !cfg!(feature = "release-unchecked");
 
-        for file in files {
-            unsafe { file.run_on_function(index, arguments.into_raw(), arguments.values.len())? };
+        if instrumented {
+            memory::check_globals(mod_name, file);
         }
+        crash_report::record_call(mod_name, file_name, on_function_name);
+        let _current_mod = stdlib::enter_mod(mod_name);
 
-        Ok(())
+        let runtime_errors = snapshot::runtime_error_count();
+        let start = instrumented.then(Instant::now);
+
+        unsafe { file.run_on_function(index, arguments.into_raw(), arguments.values.len())? };
+
+        let time = start.map(|x| x.elapsed()).unwrap_or_default();
+        if instrumented {
+            snapshot::add_cpu_time(mod_name, time);
+            if perf_guard::is_recording() {
+                perf_guard::record(mod_name, file_name, on_function_name, time);
+            }
+        }
+
+        let status = match snapshot::runtime_error_count() != runtime_errors {
+            true => {
+                headless::note_runtime_error(self, mod_name, file_name);
+                FileStatus::Errored(snapshot::last_runtime_error())
+            }
+            false => FileStatus::Executed,
+        };
+
+        Ok((status, time))
     }
 
     /// Get a list of grug files based on the name of an entity.
@@ -359,8 +675,25 @@ impl Grug {
     /// # Safety
     /// This is only self because we want to ensure grug is initialized
     pub fn get_files_by_entity_type<S: ToString>(&self, name: S) -> Vec<GrugFile> {
+        self.get_files_and_mods_by_entity_type(name)
+            .into_iter()
+            .map(|(_, file)| file)
+            .collect()
+    }
+
+    /// Like `get_files_by_entity_type`, along with the name of the mod every file is in
+    fn get_files_and_mods_by_entity_type<S: ToString>(&self, name: S) -> Vec<(&[u8], GrugFile)> {
         let name = name.to_string();
 
+        // Compared as bytes, so a broken entity type can't match by accident
+        self.get_files_and_mods()
+            .into_iter()
+            .filter(|(_, file)| unsafe { bytes_from_ptr(file.inner.entity_type) } == Some(name.as_bytes()))
+            .collect()
+    }
+
+    /// Every loaded file along with the name of the mod it's in, in load order
+    pub(crate) fn get_files_and_mods(&self) -> Vec<(&[u8], GrugFile)> {
         #[allow(static_mut_refs)]
         let mods = unsafe { grug_mods }; // SAFETY: This implements the copy trait so it's safe to use
         let mods = unsafe { from_raw_parts(mods.dirs, mods.dirs_size) };
@@ -368,28 +701,112 @@ impl Grug {
         let mut return_files = vec![];
 
         for mod_ in mods.iter() {
+            let mod_name = unsafe { bytes_from_ptr(mod_.name) }.unwrap_or_default();
             let files = unsafe { from_raw_parts(mod_.files, mod_.files_size) };
             for file in files {
-                let mod_entity_name = unsafe {
-                    CStr::from_ptr(file.entity_type)
-                        .to_string_lossy()
-                        .into_owned()
-                };
-                if mod_entity_name == name {
-                    return_files.push(GrugFile::new(*file));
-                }
+                return_files.push((mod_name, GrugFile::new(*file)));
             }
         }
 
+        self.sort_by_load_order(&mut return_files);
+
         return_files
     }
 }
 
+#[cfg(all(feature = "alloc-tracking", not(feature = "release-unchecked")))]
+impl Drop for Grug {
+    fn drop(&mut self) {
+        alloc_tracking::check_for_leaks();
+    }
+}
+
+/// Maps every on_function of `entity` to its index in a file's `on_fns`
+fn on_function_indices(entity: &Entity) -> HashMap<String, usize> {
+    entity
+        .on_functions
+        .keys()
+        .enumerate()
+        .map(|(i, name)| (name.clone(), i))
+        .collect()
+}
+
 /// An opaque grug type
+///
+/// This is what every argument gets passed to grug as.
+/// It holds i32s and bools themselves, since grug takes those by value,
+/// and a pointer for everything else.
 #[repr(C)]
 #[derive(Clone, Copy)]
 pub struct OpaqueGrugType {
-    pub raw: *mut c_void,
+    raw: *mut c_void,
+}
+
+impl OpaqueGrugType {
+    /// # Safety
+    /// `raw` has to point to a value grug can take as an argument, for as long as this is used
+    pub unsafe fn from_raw(raw: *mut c_void) -> Self {
+        Self { raw }
+    }
+
+    pub fn from_i32(value: i32) -> Self {
+        Self {
+            raw: value as u32 as usize as *mut c_void,
+        }
+    }
+
+    /// Used to pass a pointer to `value`, which grug read as the value itself
+    #[deprecated = "grug takes i32s by value, use `from_i32`"]
+    pub fn from_i32_ref(value: &mut i32) -> Self {
+        Self::from_i32(*value)
+    }
+
+    /// grug takes f32s in float registers, which an `OpaqueGrugType` can't be passed in,
+    /// so dispatching with an f32 argument returns `GrugError::UnsupportedF32Argument` for now
+    pub fn from_f32_ref(value: &mut f32) -> Self {
+        Self {
+            raw: value as *mut f32 as *mut c_void,
+        }
+    }
+
+    pub fn from_bool(value: bool) -> Self {
+        Self {
+            raw: value as usize as *mut c_void,
+        }
+    }
+
+    /// Used to pass a pointer to `value`, which grug read as the value itself
+    #[deprecated = "grug takes bools by value, use `from_bool`"]
+    pub fn from_bool_ref(value: &mut bool) -> Self {
+        Self::from_bool(*value)
+    }
+
+    pub fn from_c_str(value: &CStr) -> Self {
+        Self {
+            raw: value.as_ptr() as *mut c_void,
+        }
+    }
+
+    pub fn from_custom(value: &CustomValue) -> Self {
+        Self { raw: value.raw }
+    }
+
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.raw
+    }
+
+    /// Gets the custom value this points to, if it was made from a `CustomValue` of type `T`
+    /// that is still alive.
+    ///
+    /// # Safety
+    /// There can't be any other reference to the value while the returned one is alive.
+    pub unsafe fn as_custom<'a, T: Any>(&self) -> Option<&'a mut T> {
+        if grug_value::custom_type_of(self.raw) != Some(TypeId::of::<T>()) {
+            return None;
+        }
+
+        Some(unsafe { &mut *(self.raw as *mut T) })
+    }
 }
 
 pub struct GrugFile {
@@ -401,6 +818,15 @@ impl GrugFile {
         Self { inner: file }
     }
 
+    /// Whether the file defines the on_function at `index`, since grug leaves the ones it doesn't null
+    ///
+    /// # SAFETY
+    /// Will segfault if you put an invalid index.
+    pub unsafe fn defines_on_function(&self, index: usize) -> bool {
+        let on_fns = self.inner.on_fns as *const *const c_void;
+        !on_fns.is_null() && !unsafe { *on_fns.add(index) }.is_null()
+    }
+
     /// # SAFETY
     /// Will segfault if you put an invalid index.
     ///
@@ -414,13 +840,12 @@ impl GrugFile {
         let ptr = self.inner.on_fns as *mut unsafe extern "C" fn(*mut c_void);
         let func = unsafe { from_raw_parts_mut(ptr, index + 1) }.last_mut();
 
-        if func.is_none() {
+        if func.is_none() || !unsafe { self.defines_on_function(index) } {
             // Ensure the function actually has a definition
             return Err(GrugError::UndefinedFunction);
         }
 
-        let globals = unsafe { alloc(Layout::array::<u8>(self.inner.globals_size).unwrap()) };
-        unsafe { (self.inner.init_globals_fn.unwrap())(globals as *mut c_void, 0) };
+        let (globals, layout) = unsafe { self.init_globals() };
 
         let func = func.unwrap() as *mut unsafe extern "C" fn(*mut c_void);
 
@@ -428,7 +853,7 @@ impl GrugFile {
             let args = from_raw_parts(arguments, arguments_len);
             seq!(N in 1..3 {
                 match arguments_len {
-                    0 => (*func)(null_mut()),
+                    0 => (*func)(globals as *mut c_void),
                     #(N => {
                         seq!(M in 0..N {
                             let func = func as *mut unsafe extern "C" fn(*mut c_void, #(OpaqueGrugType,)*);
@@ -440,6 +865,34 @@ impl GrugFile {
             })
         }
 
+        unsafe { Self::free_globals(globals, layout) };
+
         Ok(())
     }
+
+    /// Allocates the globals of the file and initializes them, they have to be freed with `free_globals`
+    ///
+    /// # Safety
+    /// The file has to be loaded
+    pub(crate) unsafe fn init_globals(&self) -> (*mut u8, Layout) {
+        // Allocating zero bytes is undefined behavior, and globals can hold 8 byte values
+        let layout = Layout::from_size_align(self.inner.globals_size.max(1), 16).unwrap();
+        let globals = unsafe { alloc(layout) };
+        track(
+            globals as usize,
+            AllocationKind::Globals,
+            || unsafe { display_ptr(self.inner.name, "<unknown file>") },
+            layout.size(),
+        );
+        unsafe { (self.inner.init_globals_fn.unwrap())(globals as *mut c_void, 0) };
+
+        (globals, layout)
+    }
+
+    /// # Safety
+    /// `globals` and `layout` have to come from `init_globals`
+    pub(crate) unsafe fn free_globals(globals: *mut u8, layout: Layout) {
+        unsafe { dealloc(globals, layout) };
+        untrack(globals as usize);
+    }
 }
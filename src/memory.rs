@@ -0,0 +1,81 @@
+//! Warnings for scripts that use a lot of memory, before they become a problem
+//!
+//! grug reports how large the globals of every file are, which get allocated every time one of its on_functions runs.
+//! It doesn't report stack usage, so only globals can be checked.
+//!
+//! # Example
+//! ```rs
+//! grug.set_globals_threshold(64 * 1024, |warning| {
+//!     eprintln!("{} has {} bytes of globals", warning.file, warning.globals_size);
+//! });
+//! ```
+
+use std::{collections::HashSet, sync::Mutex};
+
+use crate::{Grug, GrugFile, ffi_string::display_ptr};
+
+/// A file whose globals are larger than the threshold
+#[derive(Debug, Clone)]
+pub struct MemoryWarning {
+    pub mod_name: String,
+    pub file: String,
+    pub globals_size: usize,
+    pub threshold: usize,
+}
+
+type WarningCallback = Box<dyn Fn(&MemoryWarning) + Send>;
+
+struct Threshold {
+    globals_size: usize,
+    callback: WarningCallback,
+    /// Files that have been warned about, so every file only gets warned about once
+    warned: HashSet<(String, String)>,
+}
+
+static THRESHOLD: Mutex<Option<Threshold>> = Mutex::new(None);
+
+impl Grug {
+    /// Calls `callback` the first time an on_function of a file with more than `globals_size` bytes of globals runs
+    pub fn set_globals_threshold<F>(&self, globals_size: usize, callback: F)
+    where
+        F: Fn(&MemoryWarning) + Send + 'static,
+    {
+        *THRESHOLD.lock().unwrap() = Some(Threshold {
+            globals_size,
+            callback: Box::new(callback),
+            warned: HashSet::new(),
+        });
+    }
+
+    pub fn clear_globals_threshold(&self) {
+        *THRESHOLD.lock().unwrap() = None;
+    }
+}
+
+/// Called before every on_function runs
+pub(crate) fn check_globals(mod_name: &[u8], file: &GrugFile) {
+    let mut threshold = THRESHOLD.lock().unwrap();
+    let Some(threshold) = &mut *threshold else {
+        return;
+    };
+
+    if file.inner.globals_size <= threshold.globals_size {
+        return;
+    }
+
+    let mod_name = String::from_utf8_lossy(mod_name).to_string();
+    let file_name = unsafe { display_ptr(file.inner.name, "<unknown file>") };
+    if !threshold
+        .warned
+        .insert((mod_name.clone(), file_name.clone()))
+    {
+        return;
+    }
+
+    (threshold.callback)(&MemoryWarning {
+        mod_name,
+        file: file_name,
+        globals_size: file.inner.globals_size,
+        threshold: threshold.globals_size,
+    });
+}
@@ -0,0 +1,294 @@
+//! Renaming entities and on_functions in installed mods, so players' mods keep working after the mod API changes
+//!
+//! Meant to run before `Grug::new`, like when the game starts for the first time after an update.
+//! Every file that gets rewritten is backed up next to itself first, as `<file>.grug.bak`,
+//! which grug ignores since it only looks at files ending in `.grug`.
+//! A backup is never replaced, so it keeps the file from before its first migration.
+//!
+//! # Example
+//! ```rs
+//! let renames = [
+//!     Rename::Entity { old: "Gun".to_string(), new: "Weapon".to_string() },
+//!     Rename::OnFunction {
+//!         entity: "Weapon".to_string(),
+//!         old: "on_fire".to_string(),
+//!         new: "on_shoot".to_string(),
+//!     },
+//! ];
+//!
+//! for file in migrate::dry_run("mods", &renames)?.files {
+//!     println!("{}: {:?} -> {:?}", file.mod_name, file.path, file.new_path);
+//! }
+//! migrate::rename_all("mods", &renames)?;
+//! ```
+
+use std::{
+    collections::HashSet,
+    ffi::OsString,
+    fs::{copy, read_dir, read_to_string, remove_file, write},
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::{GrugError, walk::collect_grug_files};
+
+/// Added to the name of a file for its backup
+const BACKUP_SUFFIX: &str = ".bak";
+
+/// A change to the mod API that installed mods have to follow
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rename {
+    /// Renames files like `ak47-Gun.grug` to `ak47-Weapon.grug`
+    Entity { old: String, new: String },
+    /// Renames the on_function `old` in every file of `entity`
+    OnFunction {
+        entity: String,
+        old: String,
+        new: String,
+    },
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MigrationReport {
+    /// Only the files that changed, sorted by mod and path
+    pub files: Vec<MigratedFile>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MigratedFile {
+    pub mod_name: String,
+    /// Relative to the mod folder
+    pub path: PathBuf,
+    /// Differs from `path` when the entity of the file got renamed
+    pub new_path: PathBuf,
+    /// How many on_function definitions got renamed
+    pub renamed_on_functions: usize,
+    /// `None` for dry runs
+    pub backup: Option<PathBuf>,
+}
+
+/// A file that needs rewriting, and what it gets rewritten to
+struct Migration {
+    file: MigratedFile,
+    source: String,
+}
+
+/// Applies `rename` to every mod in `mods_dir`
+pub fn rename<P: AsRef<Path>>(mods_dir: P, rename: Rename) -> Result<MigrationReport, GrugError> {
+    rename_all(mods_dir, &[rename])
+}
+
+/// Applies `renames` in order to every mod in `mods_dir`, so a later rename sees the entity names of an earlier one
+///
+/// Nothing gets written when any of the renamed files would replace a file that already exists,
+/// or when two files would get renamed to the same file.
+/// A backup of an earlier migration gets kept.
+pub fn rename_all<P: AsRef<Path>>(
+    mods_dir: P,
+    renames: &[Rename],
+) -> Result<MigrationReport, GrugError> {
+    let mods_dir = mods_dir.as_ref();
+    let migrations = plan(mods_dir, renames)?;
+
+    let mut report = MigrationReport::default();
+    for Migration { mut file, source } in migrations {
+        let mod_folder = mods_dir.join(&file.mod_name);
+        let path = mod_folder.join(&file.path);
+        let new_path = mod_folder.join(&file.new_path);
+
+        let backup = backup_path(&path);
+        if !backup.exists() {
+            copy(&path, &backup).map_err(|x| write_error(&backup, x))?;
+        }
+        write(&new_path, source).map_err(|x| write_error(&new_path, x))?;
+        if new_path != path {
+            remove_file(&path).map_err(|x| write_error(&path, x))?;
+        }
+
+        file.backup = Some(backup);
+        report.files.push(file);
+    }
+
+    Ok(report)
+}
+
+/// Reports what `rename_all` would change, without changing anything
+pub fn dry_run<P: AsRef<Path>>(
+    mods_dir: P,
+    renames: &[Rename],
+) -> Result<MigrationReport, GrugError> {
+    let files = plan(mods_dir.as_ref(), renames)?
+        .into_iter()
+        .map(|x| x.file)
+        .collect();
+
+    Ok(MigrationReport { files })
+}
+
+fn plan(mods_dir: &Path, renames: &[Rename]) -> Result<Vec<Migration>, GrugError> {
+    check_renames(renames)?;
+
+    if !mods_dir.is_dir() {
+        return Err(GrugError::NotAModsFolder {
+            path: mods_dir.to_path_buf(),
+        });
+    }
+
+    let mut mod_folders: Vec<_> = read_dir(mods_dir)
+        .map_err(|x| read_error(mods_dir, x))?
+        .flatten()
+        .map(|x| x.path())
+        .filter(|x| x.is_dir())
+        .collect();
+    mod_folders.sort();
+
+    let mut migrations = vec![];
+    let mut new_paths = HashSet::new();
+    for mod_folder in mod_folders {
+        let mod_name = mod_folder
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+
+        let mut paths = vec![];
+        collect_grug_files(&mod_folder, &mut paths);
+        paths.sort();
+
+        for path in paths {
+            let Some(migration) = migrate_file(&mod_folder, &mod_name, &path, renames)? else {
+                continue;
+            };
+
+            let new_path = mod_folder.join(&migration.file.new_path);
+            if (new_path != path && new_path.exists()) || !new_paths.insert(new_path.clone()) {
+                return Err(GrugError::MigrationConflict { path: new_path });
+            }
+
+            migrations.push(migration);
+        }
+    }
+
+    Ok(migrations)
+}
+
+/// Catches renames that would leave mods grug can't load
+fn check_renames(renames: &[Rename]) -> Result<(), GrugError> {
+    for rename in renames {
+        match rename {
+            Rename::Entity { new, .. } => {
+                // Same rule as `Grug::register_entity_type`
+                let is_pascal_case = new.starts_with(|x: char| x.is_ascii_uppercase())
+                    && new.chars().all(|x| x.is_ascii_alphanumeric());
+                if !is_pascal_case {
+                    return Err(GrugError::InvalidEntityName {
+                        entity_name: new.clone(),
+                    });
+                }
+            }
+            Rename::OnFunction { old, new, .. } => {
+                if let Some(name) = [old, new].into_iter().find(|x| !x.starts_with("on_")) {
+                    return Err(GrugError::NotAnOnFunction {
+                        function_name: name.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns `None` when none of `renames` apply to the file
+fn migrate_file(
+    mod_folder: &Path,
+    mod_name: &str,
+    path: &Path,
+    renames: &[Rename],
+) -> Result<Option<Migration>, GrugError> {
+    let file_name = path.file_name().unwrap().to_string_lossy();
+    let stem = file_name.strip_suffix(".grug").unwrap_or(&file_name);
+    // Same rule as grug, the entity type is everything after the first dash.
+    // Files without one don't load, so there's nothing to migrate.
+    let Some((name, entity)) = stem.split_once('-') else {
+        return Ok(None);
+    };
+
+    let mut source = read_to_string(path).map_err(|x| read_error(path, x))?;
+    let mut new_entity = entity.to_string();
+    let mut renamed_on_functions = 0;
+
+    for rename in renames {
+        match rename {
+            Rename::Entity { old, new } if new_entity == *old => {
+                new_entity = new.clone();
+            }
+            Rename::OnFunction { entity, old, new } if new_entity == *entity => {
+                let (renamed, count) = rename_on_function(&source, old, new);
+                source = renamed;
+                renamed_on_functions += count;
+            }
+            _ => {}
+        }
+    }
+
+    if new_entity == entity && renamed_on_functions == 0 {
+        return Ok(None);
+    }
+
+    let path = path.strip_prefix(mod_folder).unwrap().to_path_buf();
+    let new_path = path.with_file_name(format!("{name}-{new_entity}.grug"));
+
+    Ok(Some(Migration {
+        file: MigratedFile {
+            mod_name: mod_name.to_string(),
+            path,
+            new_path,
+            renamed_on_functions,
+            backup: None,
+        },
+        source,
+    }))
+}
+
+/// Renames the definition of the on_function `old`, returning the new source and how many definitions got renamed
+///
+/// on_functions are always defined at the start of a line, and can't be called by scripts,
+/// so this never touches comments or strings that happen to mention them.
+fn rename_on_function(source: &str, old: &str, new: &str) -> (String, usize) {
+    let mut count = 0;
+    let renamed = source
+        .split_inclusive('\n')
+        .map(|line| match line.strip_prefix(old) {
+            Some(rest) if rest.starts_with('(') => {
+                count += 1;
+                format!("{new}{rest}")
+            }
+            _ => line.to_string(),
+        })
+        .collect();
+
+    (renamed, count)
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    let mut backup = OsString::from(path.as_os_str());
+    backup.push(BACKUP_SUFFIX);
+
+    PathBuf::from(backup)
+}
+
+fn read_error(path: &Path, error: std::io::Error) -> GrugError {
+    GrugError::ReadModFile {
+        path: path.to_path_buf(),
+        error: error.to_string(),
+    }
+}
+
+fn write_error(path: &Path, error: std::io::Error) -> GrugError {
+    GrugError::WriteModFile {
+        path: path.to_path_buf(),
+        error: error.to_string(),
+    }
+}
@@ -16,13 +16,24 @@ pub struct Entity {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GameFunction {
     pub description: String,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub return_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub arguments: Vec<Argument>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Argument {
     pub name: String,
     #[serde(rename(serialize = "type", deserialize = "type"))]
     pub type_: String,
 }
+
+impl Argument {
+    pub fn new(name: &str, type_: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            type_: type_.to_string(),
+        }
+    }
+}
@@ -0,0 +1,281 @@
+//! A portable description of an installed mod, for launchers and workshops to distribute mods with
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{metadata, read_dir, read_to_string},
+    path::{Path, PathBuf},
+};
+
+use linked_hash_map::LinkedHashMap;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    Grug, GrugError,
+    hash::{hash_bytes, hash_file},
+};
+
+/// Version of the `ModPackage` format, bumped whenever it changes in a way older tooling can't read
+pub const PACKAGE_FORMAT_VERSION: u32 = 1;
+
+/// The `about.json` of a mod
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ModInfo {
+    pub name: String,
+    pub version: String,
+    /// The game version the mod requires
+    pub game_version: String,
+    pub author: String,
+    /// Any fields after the 4 grug requires
+    #[serde(flatten)]
+    pub extra: LinkedHashMap<String, serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ModPackage {
+    pub format_version: u32,
+    pub info: ModInfo,
+    /// Hash over the path and hash of every file, so two packages with the same content hash are identical
+    pub content_hash: String,
+    pub files: Vec<PackageFile>,
+    /// Every game function the mod's grug files call
+    pub capabilities: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PackageFile {
+    /// Relative to the mod folder, always separated with `/`
+    pub path: String,
+    pub hash: String,
+    pub size: u64,
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ManifestMismatch {
+    #[error("`{path}` is in the manifest, but not installed")]
+    MissingFile { path: String },
+    #[error("`{path}` is installed, but not in the manifest")]
+    ExtraFile { path: String },
+    #[error("`{path}` is different from the one in the manifest")]
+    ChangedFile { path: String },
+    #[error("The manifest is for `{expected}`, but the installed mod is `{found}`")]
+    DifferentMod { expected: String, found: String },
+    #[error(
+        "The manifest has format version `{found}`, but only `{PACKAGE_FORMAT_VERSION}` is supported"
+    )]
+    UnsupportedFormat { found: u32 },
+}
+
+impl Grug {
+    /// Describes the installed mod `mod_name`, from its `about.json` and files
+    ///
+    /// # Example
+    /// ```rs
+    /// let manifest = grug.export_manifest("guns")?;
+    /// write("guns.package.json", serde_json::to_string_pretty(&manifest)?)?;
+    /// ```
+    pub fn export_manifest<S: ToString>(&self, mod_name: S) -> Result<ModPackage, GrugError> {
+        let mod_folder = self.source_mods_folder().join(mod_name.to_string());
+
+        let about_path = mod_folder.join("about.json");
+        let about_json = read_to_string(&about_path).map_err(|x| GrugError::ReadModFile {
+            path: about_path.clone(),
+            error: x.to_string(),
+        })?;
+        let info: ModInfo =
+            serde_json::from_str(&about_json).map_err(|x| GrugError::Deserialize {
+                path: about_path.clone(),
+                error: x.to_string(),
+            })?;
+
+        let files = package_files(&mod_folder)?;
+
+        let mut capabilities = BTreeSet::new();
+        for file in files.iter().filter(|x| x.path.ends_with(".grug")) {
+            let path = mod_folder.join(&file.path);
+            let source = read_to_string(&path).map_err(|x| GrugError::ReadModFile {
+                path,
+                error: x.to_string(),
+            })?;
+
+            capabilities.extend(
+                called_functions(&source)
+                    .filter(|x| self.mod_api.game_functions.contains_key(*x))
+                    .map(|x| x.to_string()),
+            );
+        }
+
+        Ok(ModPackage {
+            format_version: PACKAGE_FORMAT_VERSION,
+            info,
+            content_hash: content_hash(&files),
+            files,
+            capabilities: capabilities.into_iter().collect(),
+        })
+    }
+
+    /// Compares the installed mod `mod_name` with `manifest`, returning every difference
+    ///
+    /// An empty list means the mod is installed exactly as described.
+    pub fn verify_against_manifest<S: ToString>(
+        &self,
+        mod_name: S,
+        manifest: &ModPackage,
+    ) -> Result<Vec<ManifestMismatch>, GrugError> {
+        if manifest.format_version != PACKAGE_FORMAT_VERSION {
+            return Ok(vec![ManifestMismatch::UnsupportedFormat {
+                found: manifest.format_version,
+            }]);
+        }
+
+        let installed = self.export_manifest(mod_name)?;
+
+        let mut mismatches = vec![];
+
+        if installed.info.name != manifest.info.name {
+            mismatches.push(ManifestMismatch::DifferentMod {
+                expected: manifest.info.name.clone(),
+                found: installed.info.name.clone(),
+            });
+        }
+
+        if installed.content_hash == manifest.content_hash {
+            return Ok(mismatches);
+        }
+
+        let installed_files: BTreeMap<&str, &PackageFile> = installed
+            .files
+            .iter()
+            .map(|x| (x.path.as_str(), x))
+            .collect();
+        let expected_files: BTreeMap<&str, &PackageFile> = manifest
+            .files
+            .iter()
+            .map(|x| (x.path.as_str(), x))
+            .collect();
+
+        for (path, expected) in &expected_files {
+            match installed_files.get(path) {
+                None => mismatches.push(ManifestMismatch::MissingFile {
+                    path: path.to_string(),
+                }),
+                Some(installed) if installed.hash != expected.hash => {
+                    mismatches.push(ManifestMismatch::ChangedFile {
+                        path: path.to_string(),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+
+        for path in installed_files.keys() {
+            if !expected_files.contains_key(path) {
+                mismatches.push(ManifestMismatch::ExtraFile {
+                    path: path.to_string(),
+                });
+            }
+        }
+
+        Ok(mismatches)
+    }
+}
+
+/// Every file in `mod_folder`, sorted by path
+fn package_files(mod_folder: &Path) -> Result<Vec<PackageFile>, GrugError> {
+    let mut paths = vec![];
+    collect_files(mod_folder, &mut paths).map_err(|x| GrugError::ReadModFile {
+        path: mod_folder.to_path_buf(),
+        error: x.to_string(),
+    })?;
+
+    let mut files = vec![];
+    for path in paths {
+        let read_error = |error: String| GrugError::ReadModFile {
+            path: path.clone(),
+            error,
+        };
+
+        let hash = hash_file(&path).ok_or_else(|| read_error("Failed to read".to_string()))?;
+        let size = metadata(&path)
+            .map_err(|x| read_error(x.to_string()))?
+            .len();
+
+        let relative_path = path.strip_prefix(mod_folder).unwrap();
+        let relative_path: Vec<_> = relative_path
+            .components()
+            .map(|x| x.as_os_str().to_string_lossy())
+            .collect();
+
+        files.push(PackageFile {
+            path: relative_path.join("/"),
+            hash,
+            size,
+        });
+    }
+
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(files)
+}
+
+/// Unlike `walk::collect_files`, fails on folders that can't be read, so a package never misses files
+fn collect_files(dir: &Path, paths: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, paths)?;
+        } else {
+            paths.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn content_hash(files: &[PackageFile]) -> String {
+    let mut bytes = vec![];
+    for file in files {
+        bytes.extend(file.path.as_bytes());
+        bytes.push(0);
+        bytes.extend(file.hash.as_bytes());
+        bytes.push(b'\n');
+    }
+
+    hash_bytes(&bytes)
+}
+
+/// Every name in `source` that's followed by a `(`, skipping comments and strings
+fn called_functions(source: &str) -> impl Iterator<Item = &str> {
+    source.lines().flat_map(|line| {
+        let mut names = vec![];
+        let mut in_string = false;
+        let mut name_start = None;
+
+        for (i, c) in line.char_indices() {
+            if in_string {
+                in_string = c != '"';
+                continue;
+            }
+
+            if c.is_ascii_alphanumeric() || c == '_' {
+                name_start.get_or_insert(i);
+                continue;
+            }
+
+            if c == '('
+                && let Some(start) = name_start
+            {
+                names.push(&line[start..i]);
+            }
+            name_start = None;
+
+            match c {
+                '"' => in_string = true,
+                '#' => break,
+                _ => {}
+            }
+        }
+
+        names
+    })
+}
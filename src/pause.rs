@@ -0,0 +1,32 @@
+//! Halting every script at once, for pause menus and the like
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::Grug;
+
+static PAUSED: AtomicBool = AtomicBool::new(false);
+
+impl Grug {
+    /// Makes `activate_on_function` do nothing until `resume` gets called
+    ///
+    /// # Example
+    /// ```rs
+    /// if pause_menu_opened {
+    ///     grug.pause();
+    /// }
+    ///
+    /// // Doesn't run any scripts while the pause menu is open
+    /// grug.activate_on_function("World", "on_update", &mut Arguments::empty())?;
+    /// ```
+    pub fn pause(&self) {
+        PAUSED.store(true, Ordering::Relaxed);
+    }
+
+    pub fn resume(&self) {
+        PAUSED.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        PAUSED.load(Ordering::Relaxed)
+    }
+}
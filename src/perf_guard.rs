@@ -0,0 +1,167 @@
+//! Catches scripts that got slower, for running in CI
+//!
+//! Record a baseline with `begin_perf_recording` and `end_perf_recording`, and save it next to the game.
+//! Later runs record the same way, and `PerfBaseline::check` reports every on_function
+//! that got slower than the baseline by more than a given factor.
+//!
+//! # Example
+//! ```rs
+//! grug.begin_perf_recording();
+//! for _ in 0..1000 {
+//!     grug.activate_on_function("World", "on_update", &mut Arguments::empty())?;
+//! }
+//! let timings = grug.end_perf_recording();
+//!
+//! if record_baseline {
+//!     timings.save("perf_baseline.json")?;
+//! } else {
+//!     let report = PerfBaseline::load("perf_baseline.json")?.check(&timings, 1.5);
+//!     assert!(report.is_ok(), "{report:#?}");
+//! }
+//! ```
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{read_to_string, write},
+    path::Path,
+    sync::{
+        LazyLock, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Grug, GrugError};
+
+static RECORDING: AtomicBool = AtomicBool::new(false);
+
+/// The total time spent in, and the number of calls to, an on_function, keyed by `mod/file` and on_function name
+type Timings = HashMap<(String, String), (Duration, u32)>;
+
+static TIMINGS: LazyLock<Mutex<Timings>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// The average time every on_function of every file took, in microseconds
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PerfBaseline {
+    /// Keyed by `mod/file`, since mods can have files with the same name, and then by on_function name
+    pub timings: BTreeMap<String, BTreeMap<String, f64>>,
+}
+
+/// An on_function that got slower than its baseline allows
+#[derive(Debug, Clone)]
+pub struct PerfRegression {
+    /// As `mod/file`
+    pub file: String,
+    pub on_function: String,
+    /// In microseconds
+    pub baseline: f64,
+    /// In microseconds
+    pub current: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PerfReport {
+    pub regressions: Vec<PerfRegression>,
+}
+
+impl PerfReport {
+    pub fn is_ok(&self) -> bool {
+        self.regressions.is_empty()
+    }
+}
+
+impl PerfBaseline {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, GrugError> {
+        let path = path.as_ref().to_path_buf();
+
+        let json = read_to_string(&path).map_err(|x| GrugError::ReadPerfBaseline {
+            path: path.clone(),
+            error: x.to_string(),
+        })?;
+
+        serde_json::from_str(&json).map_err(|x| GrugError::Deserialize {
+            path,
+            error: x.to_string(),
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), GrugError> {
+        let json = serde_json::to_string_pretty(self).unwrap();
+
+        write(&path, json).map_err(|x| GrugError::WritePerfBaseline {
+            path: path.as_ref().to_path_buf(),
+            error: x.to_string(),
+        })
+    }
+
+    /// Reports every on_function in `current` that takes more than `factor` times as long as in this baseline.
+    ///
+    /// On_functions that aren't in both are skipped, since there's nothing to compare them to.
+    pub fn check(&self, current: &PerfBaseline, factor: f64) -> PerfReport {
+        let mut regressions = vec![];
+
+        for (file, on_functions) in &current.timings {
+            for (on_function, &time) in on_functions {
+                let baseline = self.timings.get(file).and_then(|x| x.get(on_function));
+
+                if let Some(&baseline) = baseline
+                    && time > baseline * factor
+                {
+                    regressions.push(PerfRegression {
+                        file: file.clone(),
+                        on_function: on_function.clone(),
+                        baseline,
+                        current: time,
+                    });
+                }
+            }
+        }
+
+        PerfReport { regressions }
+    }
+}
+
+impl Grug {
+    /// Starts timing every on_function that gets activated
+    pub fn begin_perf_recording(&self) {
+        TIMINGS.lock().unwrap().clear();
+        RECORDING.store(true, Ordering::Relaxed);
+    }
+
+    /// Stops timing, and returns the average time of every on_function activated since `begin_perf_recording`
+    pub fn end_perf_recording(&self) -> PerfBaseline {
+        RECORDING.store(false, Ordering::Relaxed);
+
+        let mut baseline = PerfBaseline::default();
+        for ((file, on_function), (total, calls)) in TIMINGS.lock().unwrap().drain() {
+            let average = total.as_secs_f64() * 1_000_000.0 / calls as f64;
+            baseline
+                .timings
+                .entry(file)
+                .or_default()
+                .insert(on_function, average);
+        }
+
+        baseline
+    }
+}
+
+pub(crate) fn is_recording() -> bool {
+    RECORDING.load(Ordering::Relaxed)
+}
+
+pub(crate) fn record(mod_name: &[u8], file_name: &[u8], on_function: &str, time: Duration) {
+    let file = format!(
+        "{}/{}",
+        String::from_utf8_lossy(mod_name),
+        String::from_utf8_lossy(file_name)
+    );
+
+    let mut timings = TIMINGS.lock().unwrap();
+    let (total, calls) = timings.entry((file, on_function.to_string())).or_default();
+
+    *total += time;
+    *calls += 1;
+}
@@ -0,0 +1,138 @@
+//! Named sets of enabled mods and their load order, for mod manager UIs
+//!
+//! Profiles get saved as json, and applied with `Grug::apply_profile`.
+//! grug loads mods in whatever order the file system lists them in,
+//! so the load order is the order grug-rs runs the files of different mods in.
+//!
+//! # Example
+//! ```rs
+//! let mut profiles = Profiles::load("profiles.json").unwrap_or_default();
+//! profiles.insert("hardcore", Profile::new(["permadeath", "guns"]));
+//! profiles.save("profiles.json")?;
+//!
+//! grug.apply_profile(profiles.get("hardcore")?)?;
+//! ```
+
+use std::{
+    fs::{read_to_string, write},
+    path::Path,
+};
+
+use linked_hash_map::LinkedHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{Grug, GrugError, GrugFile};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    /// The enabled mods, in load order
+    pub mods: Vec<String>,
+}
+
+impl Profile {
+    pub fn new<I, S>(mods: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: ToString,
+    {
+        Self {
+            mods: mods.into_iter().map(|x| x.to_string()).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Profiles {
+    pub profiles: LinkedHashMap<String, Profile>,
+}
+
+impl Profiles {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, GrugError> {
+        let path = path.as_ref().to_path_buf();
+
+        let json = read_to_string(&path).map_err(|x| GrugError::ReadProfiles {
+            path: path.clone(),
+            error: x.to_string(),
+        })?;
+
+        serde_json::from_str(&json).map_err(|x| GrugError::Deserialize {
+            path,
+            error: x.to_string(),
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), GrugError> {
+        let json = serde_json::to_string_pretty(self).unwrap();
+
+        write(&path, json).map_err(|x| GrugError::WriteProfiles {
+            path: path.as_ref().to_path_buf(),
+            error: x.to_string(),
+        })
+    }
+
+    pub fn get<S: ToString>(&self, name: S) -> Result<&Profile, GrugError> {
+        let name = name.to_string();
+        self.profiles
+            .get(&name)
+            .ok_or(GrugError::UnknownProfile { name })
+    }
+
+    /// Adds a profile, replacing any profile with the same name
+    pub fn insert<S: ToString>(&mut self, name: S, profile: Profile) {
+        self.profiles.insert(name.to_string(), profile);
+    }
+
+    pub fn remove<S: ToString>(&mut self, name: S) -> Option<Profile> {
+        self.profiles.remove(&name.to_string())
+    }
+}
+
+impl Grug {
+    /// Enables only the mods of `profile`, runs them in its load order, and regenerates the mods.
+    ///
+    /// Only works when grug was created with `Grug::new_switchable` or `Grug::new_lazy`,
+    /// since grug itself always loads every mod in its mods folder.
+    pub fn apply_profile(&self, profile: &Profile) -> Result<(), GrugError> {
+        let Some(lazy) = &self.lazy else {
+            return Err(GrugError::ModsFolderNotSwitchable);
+        };
+
+        let source_folder = self.source_mods_folder();
+        if let Some(mod_name) = profile
+            .mods
+            .iter()
+            .find(|x| !source_folder.join(x).is_dir())
+        {
+            return Err(GrugError::NotAMod {
+                mod_name: mod_name.clone(),
+            });
+        }
+
+        *lazy.profile.lock().unwrap() = Some(profile.clone());
+
+        self.regenerate_modified_mods()
+    }
+
+    /// Goes back to enabling every mod, in the order grug loads them
+    pub fn clear_profile(&self) -> Result<(), GrugError> {
+        if let Some(lazy) = &self.lazy {
+            *lazy.profile.lock().unwrap() = None;
+        }
+
+        self.regenerate_modified_mods()
+    }
+
+    /// Sorts `files` by the load order of the applied profile
+    pub(crate) fn sort_by_load_order(&self, files: &mut [(&[u8], GrugFile)]) {
+        let Some(lazy) = &self.lazy else {
+            return;
+        };
+        let Some(profile) = &*lazy.profile.lock().unwrap() else {
+            return;
+        };
+
+        files.sort_by_key(|(mod_name, _)| {
+            profile.mods.iter().position(|x| x.as_bytes() == *mod_name)
+        });
+    }
+}
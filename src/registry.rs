@@ -0,0 +1,183 @@
+//! Signatures of every game function defined with `#[game_function]`
+//!
+//! The macro puts a `GameFunctionSignature` in the `grug_rs_game_functions` linker section for every game function,
+//! which `Grug::check_game_functions` compares against `mod_api.json`.
+
+use std::{
+    collections::HashMap,
+    ffi::{CString, c_char, c_void},
+    ptr::null_mut,
+    slice::from_raw_parts,
+};
+
+use thiserror::Error;
+
+use crate::Grug;
+
+/// What `#[game_function]` records about a game function
+///
+/// Types are the grug types the Rust types map to, with every reference being an `id`.
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct GameFunctionSignature {
+    pub name: &'static str,
+    pub arguments: &'static [&'static str],
+    pub return_type: &'static str,
+}
+
+// Makes sure the section exists even when nothing registers a game function
+#[used]
+#[unsafe(link_section = "grug_rs_game_functions")]
+static EMPTY: GameFunctionSignature = GameFunctionSignature {
+    name: "",
+    arguments: &[],
+    return_type: "void",
+};
+
+unsafe extern "C" {
+    // Defined by the linker around the section, only their addresses matter
+    static __start_grug_rs_game_functions: u8;
+    static __stop_grug_rs_game_functions: u8;
+
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum GameFunctionMismatch {
+    #[error("`{name}` is in mod_api.json, but there is no game function for it")]
+    Missing { name: String },
+    #[error("`{name}` takes `{expected}` arguments in mod_api.json, but `{found}` in Rust")]
+    ArgumentCount {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    #[error("`{argument}` of `{name}` is a `{expected}` in mod_api.json, but a `{found}` in Rust")]
+    ArgumentType {
+        name: String,
+        argument: String,
+        expected: String,
+        found: String,
+    },
+    #[error("`{name}` returns `{expected}` in mod_api.json, but `{found}` in Rust")]
+    ReturnType {
+        name: String,
+        expected: String,
+        found: String,
+    },
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GameFunctionReport {
+    pub mismatches: Vec<GameFunctionMismatch>,
+    /// Game functions defined in Rust that mod_api.json doesn't declare, which is allowed but likely a mistake
+    pub unexposed: Vec<String>,
+}
+
+impl GameFunctionReport {
+    /// Whether there are no mismatches, unexposed game functions don't count
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+impl Grug {
+    /// Compares the game functions in `mod_api.json` with the ones defined in Rust.
+    ///
+    /// Meant to be called right after `Grug::new`, since grug itself only notices a missing game function
+    /// once a mod calling it gets loaded, and never notices wrong types.
+    ///
+    /// Game functions not defined with `#[game_function]` are only checked for existence.
+    ///
+    /// # Example
+    /// ```rs
+    /// let report = grug.check_game_functions();
+    /// for mismatch in &report.mismatches {
+    ///     eprintln!("{mismatch}");
+    /// }
+    /// assert!(report.is_ok());
+    /// ```
+    pub fn check_game_functions(&self) -> GameFunctionReport {
+        let registered: HashMap<&str, &GameFunctionSignature> =
+            registered_game_functions().map(|x| (x.name, x)).collect();
+
+        let mut report = GameFunctionReport::default();
+
+        for (name, function) in self.mod_api.game_functions.iter() {
+            let Some(signature) = registered.get(name.as_str()) else {
+                if !symbol_exists(&format!("game_fn_{name}")) {
+                    report
+                        .mismatches
+                        .push(GameFunctionMismatch::Missing { name: name.clone() });
+                }
+                continue;
+            };
+
+            if function.arguments.len() != signature.arguments.len() {
+                report.mismatches.push(GameFunctionMismatch::ArgumentCount {
+                    name: name.clone(),
+                    expected: function.arguments.len(),
+                    found: signature.arguments.len(),
+                });
+                continue;
+            }
+
+            for (argument, found) in function.arguments.iter().zip(signature.arguments) {
+                if grug_kind(&argument.type_) != *found {
+                    report.mismatches.push(GameFunctionMismatch::ArgumentType {
+                        name: name.clone(),
+                        argument: argument.name.clone(),
+                        expected: argument.type_.clone(),
+                        found: found.to_string(),
+                    });
+                }
+            }
+
+            let expected = function.return_type.as_deref().unwrap_or("void");
+            if grug_kind(expected) != signature.return_type {
+                report.mismatches.push(GameFunctionMismatch::ReturnType {
+                    name: name.clone(),
+                    expected: expected.to_string(),
+                    found: signature.return_type.to_string(),
+                });
+            }
+        }
+
+        report.unexposed = registered
+            .into_keys()
+            .filter(|x| !self.mod_api.game_functions.contains_key(*x))
+            .map(|x| x.to_string())
+            .collect();
+        report.unexposed.sort();
+
+        report
+    }
+}
+
+/// Every game function defined with `#[game_function]`
+fn registered_game_functions() -> impl Iterator<Item = &'static GameFunctionSignature> {
+    let start = (&raw const __start_grug_rs_game_functions).cast::<GameFunctionSignature>();
+    let stop = (&raw const __stop_grug_rs_game_functions).cast::<GameFunctionSignature>();
+
+    // SAFETY: The section only contains `GameFunctionSignature`s, and the linker puts these two around it
+    let all = unsafe { from_raw_parts(start, stop.offset_from(start) as usize) };
+
+    all.iter().filter(|x| !x.name.is_empty())
+}
+
+/// Maps a type from mod_api.json to what it is on the Rust side
+fn grug_kind(type_: &str) -> &str {
+    match type_ {
+        "void" | "bool" | "i32" | "f32" => type_,
+        "string" | "resource" | "entity" => "string",
+        _ => "id",
+    }
+}
+
+/// Whether the executable exports `symbol`, which is how grug finds game functions
+fn symbol_exists(symbol: &str) -> bool {
+    let symbol = CString::new(symbol).unwrap();
+
+    // A null handle is `RTLD_DEFAULT`
+    !unsafe { dlsym(null_mut(), symbol.as_ptr()) }.is_null()
+}
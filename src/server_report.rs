@@ -0,0 +1,53 @@
+//! Status payloads for dedicated servers, like the body of a `/mods` endpoint
+//!
+//! # Example
+//! ```rs
+//! fn mods_endpoint(snapshot: &GrugSnapshot) -> String {
+//!     serde_json::to_string(&ServerScriptReport::from(snapshot)).unwrap()
+//! }
+//! ```
+
+use serde::Serialize;
+
+use crate::snapshot::GrugSnapshot;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerScriptReport {
+    pub mods: Vec<ModReport>,
+    pub loaded_files: usize,
+    pub runtime_errors: u64,
+    pub paused: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ModReport {
+    pub name: String,
+    pub version: Option<String>,
+    pub files: usize,
+    /// Spent in on_functions of the mod, since grug got initialized
+    pub cpu_time_ms: f64,
+    pub runtime_errors: u64,
+}
+
+impl From<&GrugSnapshot> for ServerScriptReport {
+    fn from(snapshot: &GrugSnapshot) -> Self {
+        let mods = snapshot
+            .mods
+            .iter()
+            .map(|x| ModReport {
+                name: x.name.clone(),
+                version: x.version.clone(),
+                files: x.files.len(),
+                cpu_time_ms: x.cpu_time.as_secs_f64() * 1000.0,
+                runtime_errors: x.runtime_errors,
+            })
+            .collect();
+
+        Self {
+            mods,
+            loaded_files: snapshot.stats.loaded_files,
+            runtime_errors: snapshot.stats.runtime_errors,
+            paused: snapshot.stats.paused,
+        }
+    }
+}
@@ -0,0 +1,242 @@
+//! Plain owned copies of grug's state, for UI threads
+//!
+//! A `GrugSnapshot` doesn't point into grug, so it can be sent to another thread and kept around
+//! while mods keep getting reloaded.
+//!
+//! # Example
+//! ```rs
+//! let snapshot = grug.snapshot();
+//! ui_sender.send(snapshot)?;
+//! ```
+
+use std::{
+    collections::{HashMap, VecDeque},
+    ffi::c_char,
+    fs::read_to_string,
+    path::Path,
+    slice::from_raw_parts,
+    sync::{
+        LazyLock, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use grug_sys::{grug_mod_dir, grug_mods, grug_runtime_error_type};
+use serde::Serialize;
+
+use crate::{
+    ErrorHandler, Grug, GrugRuntimeError, default_runtime_error_handler, ffi_string::display_ptr,
+    package::ModInfo,
+};
+
+/// How many runtime errors snapshots hold on to
+const RECENT_ERRORS_CAPACITY: usize = 32;
+
+static RECENT_ERRORS: Mutex<VecDeque<RuntimeErrorRecord>> = Mutex::new(VecDeque::new());
+static RUNTIME_ERRORS: AtomicU64 = AtomicU64::new(0);
+
+/// The number of runtime errors every file has had, keyed by the path grug reports
+static RUNTIME_ERRORS_BY_PATH: LazyLock<Mutex<HashMap<String, u64>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// The time spent in on_functions of every mod, keyed by mod name
+static CPU_TIME: LazyLock<Mutex<HashMap<String, Duration>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// The handler passed to `Grug::new`, which `record_runtime_error` passes every error on to
+static ERROR_HANDLER: Mutex<ErrorHandler> = Mutex::new(default_runtime_error_handler);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GrugSnapshot {
+    pub mods: Vec<ModSnapshot>,
+    pub stats: SnapshotStats,
+    /// The last runtime errors, oldest first
+    pub recent_errors: Vec<RuntimeErrorRecord>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ModSnapshot {
+    pub name: String,
+    /// From `about.json`
+    pub version: Option<String>,
+    pub files: Vec<FileSnapshot>,
+    /// Spent in on_functions of the mod, since grug got initialized
+    pub cpu_time: Duration,
+    pub runtime_errors: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileSnapshot {
+    /// Relative to the mod folder
+    pub path: String,
+    pub entity_type: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SnapshotStats {
+    pub loaded_files: usize,
+    /// Every runtime error since grug got initialized
+    pub runtime_errors: u64,
+    pub paused: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RuntimeErrorRecord {
+    pub reason: String,
+    pub kind: GrugRuntimeError,
+    pub on_function: String,
+    pub path: String,
+}
+
+impl Grug {
+    /// Copies the loaded mods, stats and recent runtime errors
+    pub fn snapshot(&self) -> GrugSnapshot {
+        #[allow(static_mut_refs)]
+        let root = unsafe { grug_mods }; // SAFETY: This implements the copy trait so it's safe to use
+
+        let cpu_time = CPU_TIME.lock().unwrap().clone();
+        let errors_by_mod = self.runtime_errors_by_mod();
+
+        let mods: Vec<ModSnapshot> = unsafe { dirs_of(&root) }
+            .iter()
+            .map(|mod_dir| {
+                let name = unsafe { display_ptr(mod_dir.name, "<unknown mod>") };
+
+                let mut files = vec![];
+                unsafe { collect_files(mod_dir, "", &mut files) };
+
+                let version =
+                    read_to_string(self.source_mods_folder().join(&name).join("about.json"))
+                        .ok()
+                        .and_then(|x| serde_json::from_str::<ModInfo>(&x).ok())
+                        .map(|x| x.version);
+
+                ModSnapshot {
+                    version,
+                    files,
+                    cpu_time: cpu_time.get(&name).copied().unwrap_or_default(),
+                    runtime_errors: errors_by_mod.get(&name).copied().unwrap_or_default(),
+                    name,
+                }
+            })
+            .collect();
+
+        GrugSnapshot {
+            stats: SnapshotStats {
+                loaded_files: mods.iter().map(|x| x.files.len()).sum(),
+                runtime_errors: RUNTIME_ERRORS.load(Ordering::Relaxed),
+                paused: self.is_paused(),
+            },
+            mods,
+            recent_errors: RECENT_ERRORS.lock().unwrap().iter().cloned().collect(),
+        }
+    }
+
+    /// Adds up the runtime errors of every file, by the mod folder its path is in
+    fn runtime_errors_by_mod(&self) -> HashMap<String, u64> {
+        let mut errors_by_mod = HashMap::new();
+
+        for (path, errors) in RUNTIME_ERRORS_BY_PATH.lock().unwrap().iter() {
+            let mod_name = Path::new(path)
+                .strip_prefix(&self.mods_folder)
+                .ok()
+                .and_then(|x| x.iter().next());
+
+            if let Some(mod_name) = mod_name {
+                *errors_by_mod
+                    .entry(mod_name.to_string_lossy().to_string())
+                    .or_default() += errors;
+            }
+        }
+
+        errors_by_mod
+    }
+}
+
+/// # Safety
+/// `dir` has to come from grug
+unsafe fn dirs_of(dir: &grug_mod_dir) -> &[grug_mod_dir] {
+    // grug leaves these null until mods get regenerated for the first time
+    if dir.dirs.is_null() {
+        return &[];
+    }
+    unsafe { from_raw_parts(dir.dirs, dir.dirs_size) }
+}
+
+/// # Safety
+/// `dir` has to come from grug
+unsafe fn collect_files(dir: &grug_mod_dir, prefix: &str, files: &mut Vec<FileSnapshot>) {
+    if !dir.files.is_null() {
+        for file in unsafe { from_raw_parts(dir.files, dir.files_size) } {
+            let name = unsafe { display_ptr(file.name, "<unknown file>") };
+            files.push(FileSnapshot {
+                path: format!("{prefix}{name}"),
+                entity_type: unsafe { display_ptr(file.entity_type, "<unknown entity type>") },
+            });
+        }
+    }
+
+    for subdir in unsafe { dirs_of(dir) } {
+        let name = unsafe { display_ptr(subdir.name, "<unknown folder>") };
+        unsafe { collect_files(subdir, &format!("{prefix}{name}/"), files) };
+    }
+}
+
+pub(crate) fn add_cpu_time(mod_name: &[u8], time: Duration) {
+    let mod_name = String::from_utf8_lossy(mod_name);
+
+    let mut cpu_time = CPU_TIME.lock().unwrap();
+    match cpu_time.get_mut(&*mod_name) {
+        Some(total) => *total += time,
+        None => {
+            cpu_time.insert(mod_name.to_string(), time);
+        }
+    }
+}
+
+pub(crate) fn runtime_error_count() -> u64 {
+    RUNTIME_ERRORS.load(Ordering::Relaxed)
+}
+
+/// The last runtime error, for right after `runtime_error_count` went up
+pub(crate) fn last_runtime_error() -> RuntimeErrorRecord {
+    RECENT_ERRORS.lock().unwrap().back().cloned().unwrap()
+}
+
+pub(crate) fn set_error_handler(handler: ErrorHandler) {
+    *ERROR_HANDLER.lock().unwrap() = handler;
+}
+
+/// Handed to grug in place of the host's error handler, so snapshots can hold the recent errors
+pub(crate) unsafe extern "C" fn record_runtime_error(
+    reason: *const c_char,
+    type_: grug_runtime_error_type,
+    on_fn_name: *const c_char,
+    on_fn_path: *const c_char,
+) {
+    RUNTIME_ERRORS.fetch_add(1, Ordering::Relaxed);
+
+    let path = unsafe { display_ptr(on_fn_path, "<unknown path>") };
+    *RUNTIME_ERRORS_BY_PATH
+        .lock()
+        .unwrap()
+        .entry(path.clone())
+        .or_default() += 1;
+
+    {
+        let mut recent_errors = RECENT_ERRORS.lock().unwrap();
+        if recent_errors.len() == RECENT_ERRORS_CAPACITY {
+            recent_errors.pop_front();
+        }
+        recent_errors.push_back(RuntimeErrorRecord {
+            reason: unsafe { display_ptr(reason, "<no reason>") },
+            kind: GrugRuntimeError::from(type_),
+            on_function: unsafe { display_ptr(on_fn_name, "<unknown fn>") },
+            path,
+        });
+    }
+
+    let handler = *ERROR_HANDLER.lock().unwrap();
+    unsafe { handler(reason, type_, on_fn_name, on_fn_path) };
+}
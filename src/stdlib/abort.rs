@@ -0,0 +1,96 @@
+//! Lets long running scripts, like world generation, stop early when the player cancels
+//!
+//! Scripts poll `should_abort()` in between steps, and return once it's `true`,
+//! so they stop at a point where their state makes sense, unlike when they hit the time limit.
+//! Requests stay until `Grug::clear_abort_request` gets called, so every script still running sees them.
+//!
+//! # Example
+//! ```rs
+//! let abort = grug.abort_handle();
+//! ui.on_cancel(move || abort.request());
+//!
+//! grug.clear_abort_request();
+//! grug.activate_on_function("World", "on_generate", &mut Arguments::empty())?;
+//! ```
+//! ```grug
+//! on_generate() {
+//!     i: i32 = 0
+//!     while i < 100 {
+//!         if should_abort() {
+//!             return
+//!         }
+//!         generate_chunk(i)
+//!         i = i + 1
+//!     }
+//! }
+//! ```
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{
+    Grug,
+    mod_api_type::{GameFunction, ModAPI},
+    registry::GameFunctionSignature,
+};
+
+static ABORT_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Requests aborts from other threads, like the one handling the cancel button
+#[derive(Clone, Debug)]
+pub struct AbortHandle {
+    _private: (),
+}
+
+impl AbortHandle {
+    pub fn request(&self) {
+        ABORT_REQUESTED.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Grug {
+    /// Makes `should_abort` return `true` until `clear_abort_request` gets called
+    pub fn request_abort(&self) {
+        ABORT_REQUESTED.store(true, Ordering::Relaxed);
+    }
+
+    pub fn clear_abort_request(&self) {
+        ABORT_REQUESTED.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_abort_requested(&self) -> bool {
+        ABORT_REQUESTED.load(Ordering::Relaxed)
+    }
+
+    /// A handle that can request an abort while the thread owning `Grug` is busy running scripts
+    pub fn abort_handle(&self) -> AbortHandle {
+        AbortHandle { _private: () }
+    }
+}
+
+impl ModAPI {
+    /// Adds `should_abort`
+    pub fn add_abort(&mut self) {
+        self.game_functions.insert(
+            "should_abort".to_string(),
+            GameFunction {
+                description: "Whether the game wants long running scripts to stop early"
+                    .to_string(),
+                return_type: Some("bool".to_string()),
+                arguments: vec![],
+            },
+        );
+    }
+}
+
+#[used]
+#[unsafe(link_section = "grug_rs_game_functions")]
+static SHOULD_ABORT: GameFunctionSignature = GameFunctionSignature {
+    name: "should_abort",
+    arguments: &[],
+    return_type: "bool",
+};
+
+#[unsafe(no_mangle)]
+pub extern "C" fn game_fn_should_abort() -> bool {
+    ABORT_REQUESTED.load(Ordering::Relaxed)
+}
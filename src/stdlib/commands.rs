@@ -0,0 +1,155 @@
+//! Lets scripts ask the engine to do things, without handing them references into the engine
+//!
+//! Scripts call `emit_command(kind, a, b, c)`, which gets decoded into the host's own command type
+//! and queued in a `CommandBuffer`. The host drains the buffer once the on_function returns.
+//!
+//! # Example
+//! ```rs
+//! enum EngineCommand {
+//!     Spawn { x: f32, y: f32 },
+//!     PlaySound { id: i32 },
+//! }
+//!
+//! impl DeserializeFromArgs for EngineCommand {
+//!     fn from_args(kind: &str, [a, b, _]: [f32; 3]) -> Result<Self, String> {
+//!         match kind {
+//!             "spawn" => Ok(Self::Spawn { x: a, y: b }),
+//!             "play_sound" => Ok(Self::PlaySound { id: a as i32 }),
+//!             _ => Err(format!("Unknown command `{kind}`")),
+//!         }
+//!     }
+//! }
+//!
+//! let commands = CommandBuffer::<EngineCommand>::new();
+//! grug.activate_on_function("World", "on_update", &mut Arguments::empty())?;
+//! for command in commands.drain() {
+//!     engine.apply(command);
+//! }
+//! ```
+
+use std::{
+    any::Any,
+    ffi::c_char,
+    mem::take,
+    ptr,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    ffi_string::str_from_ptr,
+    game_function_error,
+    mod_api_type::{Argument, GameFunction, ModAPI},
+    registry::GameFunctionSignature,
+};
+
+/// Turns the arguments of `emit_command` into a command
+pub trait DeserializeFromArgs: Sized {
+    /// The error gets reported to the script that emitted the command
+    fn from_args(kind: &str, args: [f32; 3]) -> Result<Self, String>;
+}
+
+type Emit = Box<dyn Fn(&str, [f32; 3]) -> Result<(), String> + Send>;
+
+struct Sink {
+    /// The commands of the buffer that set it, so an older buffer being dropped leaves it alone
+    commands: Arc<dyn Any + Send + Sync>,
+    emit: Emit,
+}
+
+/// Where `emit_command` sends its commands, set by the live `CommandBuffer`
+static SINK: Mutex<Option<Sink>> = Mutex::new(None);
+
+/// Collects the commands scripts emit
+///
+/// Only one buffer can be live at a time, creating a new one replaces the old one.
+pub struct CommandBuffer<T> {
+    commands: Arc<Mutex<Vec<T>>>,
+}
+
+impl<T: DeserializeFromArgs + Send + 'static> CommandBuffer<T> {
+    pub fn new() -> Self {
+        let commands = Arc::new(Mutex::new(vec![]));
+
+        let sink_commands = commands.clone();
+        *SINK.lock().unwrap() = Some(Sink {
+            commands: commands.clone(),
+            emit: Box::new(move |kind, args| {
+                let command = T::from_args(kind, args)?;
+                sink_commands.lock().unwrap().push(command);
+                Ok(())
+            }),
+        });
+
+        Self { commands }
+    }
+
+    /// Takes every command emitted so far, in the order they were emitted
+    pub fn drain(&self) -> Vec<T> {
+        take(&mut *self.commands.lock().unwrap())
+    }
+}
+
+impl<T: DeserializeFromArgs + Send + 'static> Default for CommandBuffer<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for CommandBuffer<T> {
+    fn drop(&mut self) {
+        let mut sink = SINK.lock().unwrap();
+        // With `buffer = CommandBuffer::new()`, the new buffer already replaced the sink
+        if sink
+            .as_ref()
+            .is_some_and(|x| ptr::addr_eq(Arc::as_ptr(&x.commands), Arc::as_ptr(&self.commands)))
+        {
+            *sink = None;
+        }
+    }
+}
+
+impl ModAPI {
+    /// Adds `emit_command`
+    pub fn add_command_buffer(&mut self) {
+        self.game_functions.insert(
+            "emit_command".to_string(),
+            GameFunction {
+                description: "Asks the game to do something once this function returns".to_string(),
+                return_type: None,
+                arguments: vec![
+                    Argument::new("kind", "string"),
+                    Argument::new("a", "f32"),
+                    Argument::new("b", "f32"),
+                    Argument::new("c", "f32"),
+                ],
+            },
+        );
+    }
+}
+
+#[used]
+#[unsafe(link_section = "grug_rs_game_functions")]
+static EMIT_COMMAND: GameFunctionSignature = GameFunctionSignature {
+    name: "emit_command",
+    arguments: &["string", "f32", "f32", "f32"],
+    return_type: "void",
+};
+
+/// # Safety
+/// Only meant to be called by grug
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn game_fn_emit_command(kind: *const c_char, a: f32, b: f32, c: f32) {
+    let kind = match unsafe { str_from_ptr(kind) } {
+        Ok(kind) => kind,
+        Err(error) => return game_function_error(&format!("`kind` {error}")),
+    };
+
+    let result = match &*SINK.lock().unwrap() {
+        Some(sink) => (sink.emit)(kind, [a, b, c]),
+        None => Err("There is no command buffer to emit commands to".to_string()),
+    };
+
+    if let Err(error) = result {
+        game_function_error(&error);
+    }
+}
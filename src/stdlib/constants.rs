@@ -0,0 +1,260 @@
+//! Engine constants that scripts can read, so modders don't have to hardcode them
+//!
+//! grug has no constants of its own, so scripts read them by name with `get_constant_i32(name)`,
+//! `get_constant_f32`, `get_constant_bool` and `get_constant_string`.
+//! Their `mod_api.json` entries can be added with `ModAPI::add_constants`.
+//!
+//! # Example
+//! ```rs
+//! grug.define_constants(&[
+//!     ("MAX_PLAYERS", ConstantValue::from(8)),
+//!     ("GRAVITY", ConstantValue::from(9.81f32)),
+//! ]);
+//! ```
+//! ```grug
+//! on_spawn() {
+//!     set_gravity(get_constant_f32("GRAVITY"))
+//! }
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    ffi::{CString, c_char},
+    ptr::null,
+    sync::{LazyLock, Mutex},
+};
+
+use crate::{
+    Grug,
+    ffi_string::str_from_ptr,
+    game_function_error,
+    mod_api_type::{Argument, GameFunction, ModAPI},
+    registry::GameFunctionSignature,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstantValue {
+    Bool(bool),
+    I32(i32),
+    F32(f32),
+    String(String),
+}
+
+impl ConstantValue {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Self::Bool(_) => "bool",
+            Self::I32(_) => "i32",
+            Self::F32(_) => "f32",
+            Self::String(_) => "string",
+        }
+    }
+}
+
+impl From<bool> for ConstantValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<i32> for ConstantValue {
+    fn from(value: i32) -> Self {
+        Self::I32(value)
+    }
+}
+
+impl From<f32> for ConstantValue {
+    fn from(value: f32) -> Self {
+        Self::F32(value)
+    }
+}
+
+impl From<&str> for ConstantValue {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl From<String> for ConstantValue {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+/// A constant, along with the C string handed out for string constants
+struct Constant {
+    value: ConstantValue,
+    c_string: Option<CString>,
+}
+
+static CONSTANTS: LazyLock<Mutex<BTreeMap<String, Constant>>> =
+    LazyLock::new(|| Mutex::new(BTreeMap::new()));
+
+impl Grug {
+    /// Makes `constants` readable by scripts, replacing any constants with the same names
+    pub fn define_constants<S: ToString>(&self, constants: &[(S, ConstantValue)]) {
+        let mut defined = CONSTANTS.lock().unwrap();
+
+        for (name, value) in constants {
+            let c_string = match value {
+                // Constants can contain a NUL, which C strings can't
+                ConstantValue::String(value) => {
+                    Some(CString::new(value.replace('\0', "")).unwrap())
+                }
+                _ => None,
+            };
+
+            defined.insert(
+                name.to_string(),
+                Constant {
+                    value: value.clone(),
+                    c_string,
+                },
+            );
+        }
+    }
+
+    /// Every defined constant, sorted by name
+    pub fn constants(&self) -> Vec<(String, ConstantValue)> {
+        CONSTANTS
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, x)| (name.clone(), x.value.clone()))
+            .collect()
+    }
+}
+
+impl ModAPI {
+    /// Adds `get_constant_i32`, `get_constant_f32`, `get_constant_bool` and `get_constant_string`,
+    /// whose descriptions list the constants defined so far
+    pub fn add_constants(&mut self) {
+        let constants = CONSTANTS.lock().unwrap();
+
+        for type_ in ["i32", "f32", "bool", "string"] {
+            let names: Vec<&str> = constants
+                .iter()
+                .filter(|(_, x)| x.value.type_name() == type_)
+                .map(|(name, _)| name.as_str())
+                .collect();
+
+            let mut description = format!("Gets a {type_} constant of the game");
+            if !names.is_empty() {
+                description += &format!(", which are {}", names.join(", "));
+            }
+
+            self.game_functions.insert(
+                format!("get_constant_{type_}"),
+                GameFunction {
+                    description,
+                    return_type: Some(type_.to_string()),
+                    arguments: vec![Argument::new("name", "string")],
+                },
+            );
+        }
+    }
+}
+
+/// Looks up a constant, reporting a game function error to grug if it's missing or has the wrong type
+///
+/// # Safety
+/// `name` has to be null or a valid C string
+unsafe fn lookup<T>(name: *const c_char, fallback: T, get: impl Fn(&Constant) -> Option<T>) -> T {
+    let name = match unsafe { str_from_ptr(name) } {
+        Ok(name) => name,
+        Err(error) => {
+            game_function_error(&format!("The constant name {error}"));
+            return fallback;
+        }
+    };
+
+    let constants = CONSTANTS.lock().unwrap();
+    let message = match constants.get(name) {
+        Some(constant) => match get(constant) {
+            Some(value) => return value,
+            None => format!("Constant `{name}` is a `{}`", constant.value.type_name()),
+        },
+        None => format!("There is no constant `{name}`"),
+    };
+
+    game_function_error(&message);
+
+    fallback
+}
+
+#[used]
+#[unsafe(link_section = "grug_rs_game_functions")]
+static GET_CONSTANT_I32: GameFunctionSignature = GameFunctionSignature {
+    name: "get_constant_i32",
+    arguments: &["string"],
+    return_type: "i32",
+};
+
+#[used]
+#[unsafe(link_section = "grug_rs_game_functions")]
+static GET_CONSTANT_F32: GameFunctionSignature = GameFunctionSignature {
+    name: "get_constant_f32",
+    arguments: &["string"],
+    return_type: "f32",
+};
+
+#[used]
+#[unsafe(link_section = "grug_rs_game_functions")]
+static GET_CONSTANT_BOOL: GameFunctionSignature = GameFunctionSignature {
+    name: "get_constant_bool",
+    arguments: &["string"],
+    return_type: "bool",
+};
+
+#[used]
+#[unsafe(link_section = "grug_rs_game_functions")]
+static GET_CONSTANT_STRING: GameFunctionSignature = GameFunctionSignature {
+    name: "get_constant_string",
+    arguments: &["string"],
+    return_type: "string",
+};
+
+/// # Safety
+/// Only meant to be called by grug
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn game_fn_get_constant_i32(name: *const c_char) -> i32 {
+    unsafe {
+        lookup(name, 0, |x| match x.value {
+            ConstantValue::I32(x) => Some(x),
+            _ => None,
+        })
+    }
+}
+
+/// # Safety
+/// Only meant to be called by grug
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn game_fn_get_constant_f32(name: *const c_char) -> f32 {
+    unsafe {
+        lookup(name, 0.0, |x| match x.value {
+            ConstantValue::F32(x) => Some(x),
+            _ => None,
+        })
+    }
+}
+
+/// # Safety
+/// Only meant to be called by grug
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn game_fn_get_constant_bool(name: *const c_char) -> bool {
+    unsafe {
+        lookup(name, false, |x| match x.value {
+            ConstantValue::Bool(x) => Some(x),
+            _ => None,
+        })
+    }
+}
+
+/// The returned string stays valid until the constant gets redefined
+///
+/// # Safety
+/// Only meant to be called by grug
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn game_fn_get_constant_string(name: *const c_char) -> *const c_char {
+    unsafe { lookup(name, null(), |x| x.c_string.as_ref().map(|x| x.as_ptr())) }
+}
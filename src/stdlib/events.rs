@@ -0,0 +1,137 @@
+//! Lets scripts notify game systems, which receive the notifications as their own types
+//!
+//! Scripts call `emit(kind, a, b, c)`. Every subscriber of `kind` decodes the arguments into its own type,
+//! and gets it through a channel. Events nothing is subscribed to are dropped.
+//!
+//! # Example
+//! ```rs
+//! struct DamageEvent {
+//!     target: i32,
+//!     amount: f32,
+//! }
+//!
+//! impl FromGameFnArgs for DamageEvent {
+//!     fn from_args([target, amount, _]: [f32; 3]) -> Result<Self, String> {
+//!         Ok(Self { target: target as i32, amount })
+//!     }
+//! }
+//!
+//! let damage = grug.subscribe::<DamageEvent, _>("on_emit_damage");
+//! grug.activate_on_function("World", "on_update", &mut Arguments::empty())?;
+//! for event in damage.try_iter() {
+//!     health.apply(event);
+//! }
+//! ```
+
+use std::{
+    collections::HashMap,
+    ffi::c_char,
+    sync::{
+        LazyLock, Mutex,
+        mpsc::{Receiver, channel},
+    },
+};
+
+use crate::{
+    Grug,
+    ffi_string::str_from_ptr,
+    game_function_error,
+    mod_api_type::{Argument, GameFunction, ModAPI},
+    registry::GameFunctionSignature,
+};
+
+/// Turns the arguments of `emit` into an event
+pub trait FromGameFnArgs: Sized {
+    /// The error gets reported to the script that emitted the event
+    fn from_args(args: [f32; 3]) -> Result<Self, String>;
+}
+
+/// Decodes and sends an event, returning `false` once its receiver is gone
+type Subscriber = Box<dyn Fn([f32; 3]) -> Result<bool, String> + Send>;
+
+/// Every subscriber, keyed by the kind of event
+static SUBSCRIBERS: LazyLock<Mutex<HashMap<String, Vec<Subscriber>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+impl Grug {
+    /// Sends every `kind` event scripts emit from now on to the returned receiver
+    ///
+    /// Dropping the receiver unsubscribes it.
+    pub fn subscribe<T, S>(&self, kind: S) -> Receiver<T>
+    where
+        T: FromGameFnArgs + Send + 'static,
+        S: ToString,
+    {
+        let (sender, receiver) = channel();
+
+        let subscriber: Subscriber = Box::new(move |args| {
+            let event = T::from_args(args)?;
+            Ok(sender.send(event).is_ok())
+        });
+
+        SUBSCRIBERS
+            .lock()
+            .unwrap()
+            .entry(kind.to_string())
+            .or_default()
+            .push(subscriber);
+
+        receiver
+    }
+}
+
+impl ModAPI {
+    /// Adds `emit`
+    pub fn add_events(&mut self) {
+        self.game_functions.insert(
+            "emit".to_string(),
+            GameFunction {
+                description: "Notifies the game that something happened".to_string(),
+                return_type: None,
+                arguments: vec![
+                    Argument::new("kind", "string"),
+                    Argument::new("a", "f32"),
+                    Argument::new("b", "f32"),
+                    Argument::new("c", "f32"),
+                ],
+            },
+        );
+    }
+}
+
+#[used]
+#[unsafe(link_section = "grug_rs_game_functions")]
+static EMIT: GameFunctionSignature = GameFunctionSignature {
+    name: "emit",
+    arguments: &["string", "f32", "f32", "f32"],
+    return_type: "void",
+};
+
+/// # Safety
+/// Only meant to be called by grug
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn game_fn_emit(kind: *const c_char, a: f32, b: f32, c: f32) {
+    let kind = match unsafe { str_from_ptr(kind) } {
+        Ok(kind) => kind,
+        Err(error) => return game_function_error(&format!("`kind` {error}")),
+    };
+
+    let mut subscribers = SUBSCRIBERS.lock().unwrap();
+    let Some(of_kind) = subscribers.get_mut(kind) else {
+        return;
+    };
+
+    // Every subscriber gets the event, even when an earlier one fails to decode it
+    let mut error = None;
+    of_kind.retain(|subscriber| match subscriber([a, b, c]) {
+        Ok(alive) => alive,
+        Err(x) => {
+            error.get_or_insert(x);
+            true
+        }
+    });
+
+    if let Some(error) = error {
+        game_function_error(&error);
+    }
+}
@@ -0,0 +1,61 @@
+//! Game functions that grug-rs provides itself
+//!
+//! Every pack is behind its own feature, and adds its `mod_api.json` entries through a `ModAPI` method.
+
+#[cfg(feature = "abort")]
+pub mod abort;
+#[cfg(feature = "commands")]
+pub mod commands;
+#[cfg(feature = "constants")]
+pub mod constants;
+#[cfg(feature = "events")]
+pub mod events;
+#[cfg(feature = "random")]
+pub mod random;
+#[cfg(feature = "settings")]
+pub mod settings;
+#[cfg(feature = "store")]
+pub mod store;
+
+use std::{cell::Cell, marker::PhantomData};
+
+thread_local! {
+    /// The name of the mod whose script is running on this thread, null when none is
+    static CURRENT_MOD: Cell<(*const u8, usize)> = const { Cell::new((std::ptr::null(), 0)) };
+}
+
+/// Makes `current_mod` return `mod_name` on this thread, until the returned guard gets dropped
+///
+/// Set by Rust instead of read from `grug_fn_path`, since grug only sets that in safe mode.
+pub(crate) fn enter_mod(mod_name: &[u8]) -> CurrentModGuard<'_> {
+    let previous = CURRENT_MOD.replace((mod_name.as_ptr(), mod_name.len()));
+    CurrentModGuard {
+        previous,
+        _mod_name: PhantomData,
+    }
+}
+
+/// Restores the mod that was running before, for game functions that dispatch on_functions themselves
+pub(crate) struct CurrentModGuard<'a> {
+    previous: (*const u8, usize),
+    _mod_name: PhantomData<&'a [u8]>,
+}
+
+impl Drop for CurrentModGuard<'_> {
+    fn drop(&mut self) {
+        CURRENT_MOD.set(self.previous);
+    }
+}
+
+/// The mod the script that's running on this thread right now is in
+#[cfg(any(feature = "settings", feature = "store"))]
+fn current_mod() -> Option<String> {
+    let (ptr, len) = CURRENT_MOD.get();
+    if ptr.is_null() {
+        return None;
+    }
+
+    // SAFETY: The guard that set it borrows the name until it gets dropped
+    let mod_name = unsafe { std::slice::from_raw_parts(ptr, len) };
+    std::str::from_utf8(mod_name).ok().map(str::to_string)
+}
@@ -0,0 +1,163 @@
+//! Seeded randomness for scripts, so the same seed replays the same modded simulation
+//!
+//! Scripts call `rand_i32(min, max)`, `rand_f32(min, max)` and `rand_bool()`.
+//! Every instance gets its own stream, derived from the session seed and its id,
+//! so the numbers an instance gets don't depend on the order instances are dispatched in.
+//! The host picks the instance with `Grug::set_random_instance` before dispatching to it.
+//!
+//! # Example
+//! ```rs
+//! grug.seed_random(lobby.seed);
+//!
+//! for enemy in &enemies {
+//!     grug.set_random_instance(enemy.id);
+//!     grug.activate_on_function("Enemy", "on_tick", &mut Arguments::empty())?;
+//! }
+//! ```
+
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+};
+
+use crate::{
+    Grug, game_function_error,
+    mod_api_type::{Argument, GameFunction, ModAPI},
+    registry::GameFunctionSignature,
+};
+
+struct Streams {
+    seed: u64,
+    instance: u64,
+    /// The state of every instance's stream, keyed by instance id
+    states: HashMap<u64, u64>,
+}
+
+static STREAMS: LazyLock<Mutex<Streams>> = LazyLock::new(|| {
+    Mutex::new(Streams {
+        seed: 0,
+        instance: 0,
+        states: HashMap::new(),
+    })
+});
+
+impl Grug {
+    /// Restarts every stream from `seed`
+    pub fn seed_random(&self, seed: u64) {
+        let mut streams = STREAMS.lock().unwrap();
+        streams.seed = seed;
+        streams.states.clear();
+    }
+
+    /// Makes scripts draw from the stream of `instance` until another instance is set
+    pub fn set_random_instance(&self, instance: u64) {
+        STREAMS.lock().unwrap().instance = instance;
+    }
+}
+
+impl ModAPI {
+    /// Adds `rand_i32`, `rand_f32` and `rand_bool`
+    pub fn add_random(&mut self) {
+        self.game_functions.insert(
+            "rand_i32".to_string(),
+            GameFunction {
+                description: "Gets a random number from min up to and including max".to_string(),
+                return_type: Some("i32".to_string()),
+                arguments: vec![Argument::new("min", "i32"), Argument::new("max", "i32")],
+            },
+        );
+        self.game_functions.insert(
+            "rand_f32".to_string(),
+            GameFunction {
+                description: "Gets a random number from min up to max".to_string(),
+                return_type: Some("f32".to_string()),
+                arguments: vec![Argument::new("min", "f32"), Argument::new("max", "f32")],
+            },
+        );
+        self.game_functions.insert(
+            "rand_bool".to_string(),
+            GameFunction {
+                description: "Flips a coin".to_string(),
+                return_type: Some("bool".to_string()),
+                arguments: vec![],
+            },
+        );
+    }
+}
+
+/// SplitMix64, which is also what the stream of every instance gets seeded with
+fn split_mix(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// The next number of the current instance's stream
+fn next() -> u64 {
+    let mut streams = STREAMS.lock().unwrap();
+    let Streams {
+        seed,
+        instance,
+        states,
+    } = &mut *streams;
+
+    let state = states.entry(*instance).or_insert_with(|| {
+        let mut state = *seed ^ instance.wrapping_mul(0xd1b54a32d192ed03);
+        split_mix(&mut state)
+    });
+    split_mix(state)
+}
+
+#[used]
+#[unsafe(link_section = "grug_rs_game_functions")]
+static RAND_I32: GameFunctionSignature = GameFunctionSignature {
+    name: "rand_i32",
+    arguments: &["i32", "i32"],
+    return_type: "i32",
+};
+
+#[used]
+#[unsafe(link_section = "grug_rs_game_functions")]
+static RAND_F32: GameFunctionSignature = GameFunctionSignature {
+    name: "rand_f32",
+    arguments: &["f32", "f32"],
+    return_type: "f32",
+};
+
+#[used]
+#[unsafe(link_section = "grug_rs_game_functions")]
+static RAND_BOOL: GameFunctionSignature = GameFunctionSignature {
+    name: "rand_bool",
+    arguments: &[],
+    return_type: "bool",
+};
+
+#[unsafe(no_mangle)]
+pub extern "C" fn game_fn_rand_i32(min: i32, max: i32) -> i32 {
+    if min > max {
+        game_function_error(&format!("`min` {min} is larger than `max` {max}"));
+        return min;
+    }
+
+    let range = (max as i64 - min as i64 + 1) as u64;
+    (min as i64 + (next() % range) as i64) as i32
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn game_fn_rand_f32(min: f32, max: f32) -> f32 {
+    if min > max {
+        game_function_error(&format!("`min` {min} is larger than `max` {max}"));
+        return min;
+    }
+
+    // The top 24 bits, which is as many as an f32 can hold
+    let fraction = (next() >> 40) as f32 / (1u64 << 24) as f32;
+    min + (max - min) * fraction
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn game_fn_rand_bool() -> bool {
+    next() >> 63 == 1
+}
@@ -0,0 +1,446 @@
+//! Tunable values that mods declare, and that players can change from an in-game settings menu
+//!
+//! Mods declare their settings in the `settings` field of their `about.json`.
+//! grug only allows strings in there, so the defaults are written as strings too:
+//! ```json
+//! {
+//!     "name": "guns",
+//!     ...
+//!     "settings": {
+//!         "max_ammo": { "type": "i32", "default": "30", "description": "Bullets per magazine" },
+//!         "friendly_fire": { "type": "bool", "default": "false" }
+//!     }
+//! }
+//! ```
+//! The types are `i32`, `f32`, `bool` and `string`, and the description is optional.
+//!
+//! Scripts read them with `get_setting_i32(name)`, `get_setting_f32`, `get_setting_bool` and `get_setting_string`,
+//! which always read the settings of the mod the script is in.
+//! Values changed with `Grug::set_mod_setting` are saved to the dll folder, and are used from then on.
+//!
+//! # Example
+//! ```rs
+//! for setting in grug.mod_settings("guns")? {
+//!     draw_setting(&setting.name, &setting.value);
+//! }
+//!
+//! grug.set_mod_setting("guns", "max_ammo", SettingValue::I32(60))?;
+//! ```
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::{CString, c_char},
+    fs::{create_dir_all, read_to_string, write},
+    io::ErrorKind,
+    path::PathBuf,
+    ptr::null,
+    sync::{LazyLock, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::current_mod;
+use crate::{
+    Grug, GrugError,
+    ffi_string::str_from_ptr,
+    game_function_error,
+    mod_api_type::{Argument, GameFunction, ModAPI},
+    package::ModInfo,
+    registry::GameFunctionSignature,
+};
+
+/// Lives in the dll folder, and holds a json file per mod with the values the host changed
+const SETTINGS_FOLDER: &str = "mod_settings";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SettingValue {
+    Bool(bool),
+    I32(i32),
+    F32(f32),
+    String(String),
+}
+
+impl SettingValue {
+    fn parse(type_: &str, value: &str) -> Option<Self> {
+        match type_ {
+            "i32" => value.parse().ok().map(Self::I32),
+            "f32" => value.parse().ok().map(Self::F32),
+            "bool" => value.parse().ok().map(Self::Bool),
+            "string" => Some(Self::String(value.to_string())),
+            _ => None,
+        }
+    }
+
+    fn type_name(&self) -> &'static str {
+        match self {
+            Self::Bool(_) => "bool",
+            Self::I32(_) => "i32",
+            Self::F32(_) => "f32",
+            Self::String(_) => "string",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModSetting {
+    pub name: String,
+    pub description: String,
+    /// What `about.json` declares
+    pub default: SettingValue,
+    /// What scripts get
+    pub value: SettingValue,
+}
+
+/// A setting in `about.json`
+#[derive(Deserialize)]
+struct SettingDeclaration {
+    #[serde(rename = "type")]
+    type_: String,
+    default: String,
+    #[serde(default)]
+    description: String,
+}
+
+/// The settings of a mod, along with the C strings handed out for its string settings
+struct LoadedSettings {
+    settings: Vec<ModSetting>,
+    strings: HashMap<String, CString>,
+}
+
+impl LoadedSettings {
+    fn new(settings: Vec<ModSetting>) -> Self {
+        let strings = settings
+            .iter()
+            .filter_map(|x| match &x.value {
+                SettingValue::String(value) => Some((x.name.clone(), c_string(value))),
+                _ => None,
+            })
+            .collect();
+
+        Self { settings, strings }
+    }
+}
+
+/// Every loaded mod's settings, keyed by mod name
+static SETTINGS: LazyLock<Mutex<HashMap<String, LoadedSettings>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+impl Grug {
+    /// Loads the settings of every mod, so scripts can read them
+    pub fn load_mod_settings(&self) -> Result<(), GrugError> {
+        let mod_folders = self
+            .source_mods_folder()
+            .read_dir()
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|x| x.path().is_dir());
+
+        for mod_folder in mod_folders {
+            let mod_name = mod_folder.file_name().to_string_lossy().to_string();
+            self.load_settings_of(&mod_name)?;
+        }
+
+        Ok(())
+    }
+
+    /// The settings `mod_name` declares, with any values the host changed.
+    /// Loads them first, if they aren't loaded yet.
+    pub fn mod_settings<S: ToString>(&self, mod_name: S) -> Result<Vec<ModSetting>, GrugError> {
+        let mod_name = mod_name.to_string();
+
+        if let Some(loaded) = SETTINGS.lock().unwrap().get(&mod_name) {
+            return Ok(loaded.settings.clone());
+        }
+
+        self.load_settings_of(&mod_name)
+    }
+
+    /// Changes the value scripts get for a setting, and saves it to the dll folder
+    pub fn set_mod_setting<S1: ToString, S2: ToString>(
+        &self,
+        mod_name: S1,
+        name: S2,
+        value: SettingValue,
+    ) -> Result<(), GrugError> {
+        let mod_name = mod_name.to_string();
+        let name = name.to_string();
+
+        let mut settings = self.mod_settings(&mod_name)?;
+
+        let setting = settings
+            .iter_mut()
+            .find(|x| x.name == name)
+            .ok_or_else(|| GrugError::UnknownModSetting {
+                mod_name: mod_name.clone(),
+                name: name.clone(),
+            })?;
+
+        if setting.default.type_name() != value.type_name() {
+            return Err(GrugError::WrongModSettingType {
+                name,
+                expected: setting.default.type_name().to_string(),
+                got: value.type_name().to_string(),
+            });
+        }
+        // json has no NaN or infinity, so the saved settings couldn't be loaded again
+        if let SettingValue::F32(value) = value
+            && !value.is_finite()
+        {
+            return Err(GrugError::NonFiniteModSetting { name, value });
+        }
+        setting.value = value;
+
+        let overrides: BTreeMap<&str, &SettingValue> = settings
+            .iter()
+            .filter(|x| x.value != x.default)
+            .map(|x| (x.name.as_str(), &x.value))
+            .collect();
+
+        let path = self.settings_path(&mod_name);
+        let json = serde_json::to_string_pretty(&overrides).unwrap();
+        path.parent()
+            .map_or(Ok(()), create_dir_all)
+            .and_then(|_| write(&path, json))
+            .map_err(|x| GrugError::WriteModFile {
+                path,
+                error: x.to_string(),
+            })?;
+
+        SETTINGS
+            .lock()
+            .unwrap()
+            .insert(mod_name, LoadedSettings::new(settings));
+
+        Ok(())
+    }
+
+    fn settings_path(&self, mod_name: &str) -> PathBuf {
+        self.mods_dll_folder
+            .join(SETTINGS_FOLDER)
+            .join(format!("{mod_name}.json"))
+    }
+
+    /// Reads the declarations of `mod_name` and the values the host saved, and makes them available to scripts
+    fn load_settings_of(&self, mod_name: &str) -> Result<Vec<ModSetting>, GrugError> {
+        let about_path = self.source_mods_folder().join(mod_name).join("about.json");
+        let about_json = read_to_string(&about_path).map_err(|x| GrugError::ReadModFile {
+            path: about_path.clone(),
+            error: x.to_string(),
+        })?;
+        let info: ModInfo =
+            serde_json::from_str(&about_json).map_err(|x| GrugError::Deserialize {
+                path: about_path.clone(),
+                error: x.to_string(),
+            })?;
+
+        let declared: BTreeMap<String, SettingDeclaration> = match info.extra.get("settings") {
+            Some(settings) => {
+                serde_json::from_value(settings.clone()).map_err(|x| GrugError::Deserialize {
+                    path: about_path.clone(),
+                    error: x.to_string(),
+                })?
+            }
+            None => BTreeMap::new(),
+        };
+
+        // Saved values that no longer match what the mod declares are left out, since the mod may have changed since
+        let saved_path = self.settings_path(mod_name);
+        let saved: BTreeMap<String, SettingValue> = match read_to_string(&saved_path) {
+            Ok(json) => serde_json::from_str(&json).map_err(|x| GrugError::Deserialize {
+                path: saved_path,
+                error: x.to_string(),
+            })?,
+            Err(error) if error.kind() == ErrorKind::NotFound => BTreeMap::new(),
+            Err(error) => {
+                return Err(GrugError::ReadModFile {
+                    path: saved_path,
+                    error: error.to_string(),
+                });
+            }
+        };
+
+        let mut settings = vec![];
+        for (name, declaration) in declared {
+            let Some(default) = SettingValue::parse(&declaration.type_, &declaration.default)
+            else {
+                return Err(GrugError::Deserialize {
+                    path: about_path,
+                    error: format!(
+                        "Setting `{name}` its default `{}` is not a `{}`",
+                        declaration.default, declaration.type_
+                    ),
+                });
+            };
+
+            let value = saved
+                .get(&name)
+                .filter(|x| x.type_name() == default.type_name())
+                .unwrap_or(&default)
+                .clone();
+
+            settings.push(ModSetting {
+                name,
+                description: declaration.description,
+                default,
+                value,
+            });
+        }
+
+        SETTINGS
+            .lock()
+            .unwrap()
+            .insert(mod_name.to_string(), LoadedSettings::new(settings.clone()));
+
+        Ok(settings)
+    }
+}
+
+/// Forgets every loaded setting, for when the mods they came from are gone
+pub(crate) fn forget_loaded_settings() {
+    SETTINGS.lock().unwrap().clear();
+}
+
+impl ModAPI {
+    /// Adds `get_setting_i32`, `get_setting_f32`, `get_setting_bool` and `get_setting_string`
+    pub fn add_mod_settings(&mut self) {
+        for type_ in ["i32", "f32", "bool", "string"] {
+            self.game_functions.insert(
+                format!("get_setting_{type_}"),
+                GameFunction {
+                    description: format!("Gets a {type_} setting of this mod"),
+                    return_type: Some(type_.to_string()),
+                    arguments: vec![Argument::new("name", "string")],
+                },
+            );
+        }
+    }
+}
+
+fn c_string(value: &str) -> CString {
+    // Settings come from json, so they can contain a NUL, which C strings can't
+    CString::new(value.replace('\0', "")).unwrap()
+}
+
+/// Looks up a setting of the current mod, reporting a game function error to grug if it's missing or has the wrong type
+///
+/// # Safety
+/// `name` has to be null or a valid C string
+unsafe fn lookup<T>(
+    name: *const c_char,
+    fallback: T,
+    get: impl Fn(&LoadedSettings, &ModSetting) -> Option<T>,
+) -> T {
+    let name = match unsafe { str_from_ptr(name) } {
+        Ok(name) => name,
+        Err(error) => {
+            game_function_error(&format!("The setting name {error}"));
+            return fallback;
+        }
+    };
+
+    let Some(mod_name) = current_mod() else {
+        game_function_error("Couldn't find out which mod this script is in");
+        return fallback;
+    };
+
+    let settings = SETTINGS.lock().unwrap();
+    let Some(loaded) = settings.get(&mod_name) else {
+        game_function_error(&format!("The settings of `{mod_name}` haven't been loaded"));
+        return fallback;
+    };
+
+    let message = match loaded.settings.iter().find(|x| x.name == name) {
+        Some(setting) => match get(loaded, setting) {
+            Some(value) => return value,
+            None => format!("Setting `{name}` is a `{}`", setting.default.type_name()),
+        },
+        None => format!("`{mod_name}` has no setting `{name}`"),
+    };
+
+    game_function_error(&message);
+
+    fallback
+}
+
+#[used]
+#[unsafe(link_section = "grug_rs_game_functions")]
+static GET_SETTING_I32: GameFunctionSignature = GameFunctionSignature {
+    name: "get_setting_i32",
+    arguments: &["string"],
+    return_type: "i32",
+};
+
+#[used]
+#[unsafe(link_section = "grug_rs_game_functions")]
+static GET_SETTING_F32: GameFunctionSignature = GameFunctionSignature {
+    name: "get_setting_f32",
+    arguments: &["string"],
+    return_type: "f32",
+};
+
+#[used]
+#[unsafe(link_section = "grug_rs_game_functions")]
+static GET_SETTING_BOOL: GameFunctionSignature = GameFunctionSignature {
+    name: "get_setting_bool",
+    arguments: &["string"],
+    return_type: "bool",
+};
+
+#[used]
+#[unsafe(link_section = "grug_rs_game_functions")]
+static GET_SETTING_STRING: GameFunctionSignature = GameFunctionSignature {
+    name: "get_setting_string",
+    arguments: &["string"],
+    return_type: "string",
+};
+
+/// # Safety
+/// Only meant to be called by grug
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn game_fn_get_setting_i32(name: *const c_char) -> i32 {
+    unsafe {
+        lookup(name, 0, |_, x| match x.value {
+            SettingValue::I32(x) => Some(x),
+            _ => None,
+        })
+    }
+}
+
+/// # Safety
+/// Only meant to be called by grug
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn game_fn_get_setting_f32(name: *const c_char) -> f32 {
+    unsafe {
+        lookup(name, 0.0, |_, x| match x.value {
+            SettingValue::F32(x) => Some(x),
+            _ => None,
+        })
+    }
+}
+
+/// # Safety
+/// Only meant to be called by grug
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn game_fn_get_setting_bool(name: *const c_char) -> bool {
+    unsafe {
+        lookup(name, false, |_, x| match x.value {
+            SettingValue::Bool(x) => Some(x),
+            _ => None,
+        })
+    }
+}
+
+/// The returned string lives in the loaded settings, so it stays valid until the setting gets changed
+///
+/// # Safety
+/// Only meant to be called by grug
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn game_fn_get_setting_string(name: *const c_char) -> *const c_char {
+    unsafe {
+        lookup(name, null(), |loaded, x| {
+            loaded.strings.get(&x.name).map(|x| x.as_ptr())
+        })
+    }
+}
@@ -0,0 +1,425 @@
+//! A key-value store per mod that survives restarts, for progress and the like
+//!
+//! Scripts call `store_set_i32(key, value)`, `store_set_f32`, `store_set_bool` and `store_set_string`,
+//! and read the values back with `store_get_i32(key, default)` and friends, which return `default` for missing keys.
+//! Every mod only sees its own keys.
+//!
+//! Changes are kept in memory until the host calls `Grug::flush_mod_store`,
+//! which writes a json file per changed mod to the dll folder, so the host decides when saving happens.
+//!
+//! # Example
+//! ```rs
+//! grug.load_mod_store()?;
+//!
+//! loop {
+//!     grug.activate_on_function("World", "on_update", &mut Arguments::empty())?;
+//!     if autosave_timer.finished() {
+//!         grug.flush_mod_store()?;
+//!     }
+//! }
+//! ```
+//! ```grug
+//! on_level_completed(level: i32) {
+//!     if level > store_get_i32("best_level", 0) {
+//!         store_set_i32("best_level", level)
+//!     }
+//! }
+//! ```
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    ffi::c_char,
+    fs::{create_dir_all, read_dir, read_to_string, write},
+    path::PathBuf,
+    sync::{LazyLock, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::current_mod;
+use crate::{
+    Grug, GrugError,
+    ffi_string::{str_from_argument, str_from_ptr},
+    frame::alloc_c_str,
+    game_function_error,
+    mod_api_type::{Argument, GameFunction, ModAPI},
+    registry::GameFunctionSignature,
+};
+
+/// Lives in the dll folder, and holds a json file per mod with its keys
+const STORE_FOLDER: &str = "mod_store";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum StoreValue {
+    Bool(bool),
+    I32(i32),
+    F32(f32),
+    String(String),
+}
+
+impl StoreValue {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Self::Bool(_) => "bool",
+            Self::I32(_) => "i32",
+            Self::F32(_) => "f32",
+            Self::String(_) => "string",
+        }
+    }
+}
+
+#[derive(Default)]
+struct Store {
+    /// Where the json files go, `None` until `Grug::load_mod_store` gets called
+    folder: Option<PathBuf>,
+    /// Keyed by mod name, and then by key
+    values: HashMap<String, BTreeMap<String, StoreValue>>,
+    /// The mods whose keys changed since the last flush
+    changed: HashSet<String>,
+}
+
+static STORE: LazyLock<Mutex<Store>> = LazyLock::new(Default::default);
+
+impl Grug {
+    /// Loads what every mod stored, so scripts can use the store
+    pub fn load_mod_store(&self) -> Result<(), GrugError> {
+        let folder = self.mods_dll_folder.join(STORE_FOLDER);
+
+        let mut values = HashMap::new();
+        for entry in read_dir(&folder).into_iter().flatten().flatten() {
+            let path = entry.path();
+            let Some(mod_name) = path
+                .extension()
+                .is_some_and(|x| x == "json")
+                .then(|| path.file_stem())
+                .flatten()
+            else {
+                continue;
+            };
+            let mod_name = mod_name.to_string_lossy().to_string();
+
+            let json = read_to_string(&path).map_err(|x| GrugError::ReadModFile {
+                path: path.clone(),
+                error: x.to_string(),
+            })?;
+            let keys = serde_json::from_str(&json).map_err(|x| GrugError::Deserialize {
+                path: path.clone(),
+                error: x.to_string(),
+            })?;
+            values.insert(mod_name, keys);
+        }
+
+        *STORE.lock().unwrap() = Store {
+            folder: Some(folder),
+            values,
+            changed: HashSet::new(),
+        };
+
+        Ok(())
+    }
+
+    /// Writes the keys of every mod that changed them since the last flush
+    pub fn flush_mod_store(&self) -> Result<(), GrugError> {
+        let mut store = STORE.lock().unwrap();
+        let Some(folder) = store.folder.clone() else {
+            return Ok(());
+        };
+
+        let changed: Vec<String> = store.changed.iter().cloned().collect();
+        for mod_name in changed {
+            let path = folder.join(format!("{mod_name}.json"));
+            let json = serde_json::to_string_pretty(&store.values[&mod_name]).unwrap();
+            create_dir_all(&folder)
+                .and_then(|_| write(&path, json))
+                .map_err(|x| GrugError::WriteModFile {
+                    path,
+                    error: x.to_string(),
+                })?;
+
+            store.changed.remove(&mod_name);
+        }
+
+        Ok(())
+    }
+
+    /// Everything `mod_name` stored, including what hasn't been flushed yet
+    pub fn mod_store<S: ToString>(&self, mod_name: S) -> BTreeMap<String, StoreValue> {
+        STORE
+            .lock()
+            .unwrap()
+            .values
+            .get(&mod_name.to_string())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Removes every key of `mod_name`, like when a player resets a mod's progress
+    pub fn clear_mod_store<S: ToString>(&self, mod_name: S) {
+        let mod_name = mod_name.to_string();
+
+        let mut store = STORE.lock().unwrap();
+        store.values.insert(mod_name.clone(), BTreeMap::new());
+        store.changed.insert(mod_name);
+    }
+}
+
+impl ModAPI {
+    /// Adds `store_set_i32`, `store_get_i32`, and the same for `f32`, `bool` and `string`
+    pub fn add_mod_store(&mut self) {
+        for type_ in ["i32", "f32", "bool", "string"] {
+            self.game_functions.insert(
+                format!("store_set_{type_}"),
+                GameFunction {
+                    description: format!(
+                        "Stores a {type_} for this mod, which is kept between sessions"
+                    ),
+                    return_type: None,
+                    arguments: vec![
+                        Argument::new("key", "string"),
+                        Argument::new("value", type_),
+                    ],
+                },
+            );
+            self.game_functions.insert(
+                format!("store_get_{type_}"),
+                GameFunction {
+                    description: format!(
+                        "Gets a {type_} this mod stored, or `default` if it hasn't stored the key"
+                    ),
+                    return_type: Some(type_.to_string()),
+                    arguments: vec![
+                        Argument::new("key", "string"),
+                        Argument::new("default", type_),
+                    ],
+                },
+            );
+        }
+    }
+}
+
+/// Runs `f` on the keys of the current mod, reporting a game function error to grug if that isn't possible
+///
+/// # Safety
+/// `key` has to be null or a valid C string
+unsafe fn with_keys<T>(
+    key: *const c_char,
+    fallback: T,
+    f: impl FnOnce(&str, &mut BTreeMap<String, StoreValue>, &mut bool) -> Result<T, String>,
+) -> T {
+    let key = match unsafe { str_from_ptr(key) } {
+        Ok(key) => key,
+        Err(error) => {
+            game_function_error(&format!("The key {error}"));
+            return fallback;
+        }
+    };
+
+    let mut store = STORE.lock().unwrap();
+    if store.folder.is_none() {
+        game_function_error("The mod store hasn't been loaded");
+        return fallback;
+    }
+
+    let Some(mod_name) = current_mod() else {
+        game_function_error("Couldn't find out which mod this script is in");
+        return fallback;
+    };
+
+    let mut changed = false;
+    let keys = store.values.entry(mod_name.clone()).or_default();
+    let result = f(key, keys, &mut changed);
+    if changed {
+        store.changed.insert(mod_name);
+    }
+
+    result.unwrap_or_else(|message| {
+        game_function_error(&message);
+        fallback
+    })
+}
+
+/// # Safety
+/// `key` has to be null or a valid C string
+unsafe fn set(key: *const c_char, value: StoreValue) {
+    unsafe {
+        with_keys(key, (), |key, keys, changed| {
+            if keys.get(key) != Some(&value) {
+                keys.insert(key.to_string(), value);
+                *changed = true;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// # Safety
+/// `key` has to be null or a valid C string
+unsafe fn get<T>(key: *const c_char, default: T, get: impl FnOnce(&StoreValue) -> Option<T>) -> T
+where
+    T: Copy,
+{
+    unsafe {
+        with_keys(key, default, |key, keys, _| match keys.get(key) {
+            Some(value) => {
+                get(value).ok_or_else(|| format!("Stored key `{key}` is a `{}`", value.type_name()))
+            }
+            None => Ok(default),
+        })
+    }
+}
+
+#[used]
+#[unsafe(link_section = "grug_rs_game_functions")]
+static STORE_SET_I32: GameFunctionSignature = GameFunctionSignature {
+    name: "store_set_i32",
+    arguments: &["string", "i32"],
+    return_type: "void",
+};
+
+#[used]
+#[unsafe(link_section = "grug_rs_game_functions")]
+static STORE_SET_F32: GameFunctionSignature = GameFunctionSignature {
+    name: "store_set_f32",
+    arguments: &["string", "f32"],
+    return_type: "void",
+};
+
+#[used]
+#[unsafe(link_section = "grug_rs_game_functions")]
+static STORE_SET_BOOL: GameFunctionSignature = GameFunctionSignature {
+    name: "store_set_bool",
+    arguments: &["string", "bool"],
+    return_type: "void",
+};
+
+#[used]
+#[unsafe(link_section = "grug_rs_game_functions")]
+static STORE_SET_STRING: GameFunctionSignature = GameFunctionSignature {
+    name: "store_set_string",
+    arguments: &["string", "string"],
+    return_type: "void",
+};
+
+#[used]
+#[unsafe(link_section = "grug_rs_game_functions")]
+static STORE_GET_I32: GameFunctionSignature = GameFunctionSignature {
+    name: "store_get_i32",
+    arguments: &["string", "i32"],
+    return_type: "i32",
+};
+
+#[used]
+#[unsafe(link_section = "grug_rs_game_functions")]
+static STORE_GET_F32: GameFunctionSignature = GameFunctionSignature {
+    name: "store_get_f32",
+    arguments: &["string", "f32"],
+    return_type: "f32",
+};
+
+#[used]
+#[unsafe(link_section = "grug_rs_game_functions")]
+static STORE_GET_BOOL: GameFunctionSignature = GameFunctionSignature {
+    name: "store_get_bool",
+    arguments: &["string", "bool"],
+    return_type: "bool",
+};
+
+#[used]
+#[unsafe(link_section = "grug_rs_game_functions")]
+static STORE_GET_STRING: GameFunctionSignature = GameFunctionSignature {
+    name: "store_get_string",
+    arguments: &["string", "string"],
+    return_type: "string",
+};
+
+/// # Safety
+/// Only meant to be called by grug
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn game_fn_store_set_i32(key: *const c_char, value: i32) {
+    unsafe { set(key, StoreValue::I32(value)) }
+}
+
+/// # Safety
+/// Only meant to be called by grug
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn game_fn_store_set_f32(key: *const c_char, value: f32) {
+    // json has no NaN or infinity, so the store couldn't be loaded again
+    if !value.is_finite() {
+        game_function_error(&format!("Can't store `{value}`, only finite numbers"));
+        return;
+    }
+    unsafe { set(key, StoreValue::F32(value)) }
+}
+
+/// # Safety
+/// Only meant to be called by grug
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn game_fn_store_set_bool(key: *const c_char, value: bool) {
+    unsafe { set(key, StoreValue::Bool(value)) }
+}
+
+/// # Safety
+/// Only meant to be called by grug
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn game_fn_store_set_string(key: *const c_char, value: *const c_char) {
+    let value = match unsafe { str_from_argument(value) } {
+        Ok(value) => value,
+        Err(error) => return game_function_error(&format!("`value` {error}")),
+    };
+
+    unsafe { set(key, StoreValue::String(value.to_string())) }
+}
+
+/// # Safety
+/// Only meant to be called by grug
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn game_fn_store_get_i32(key: *const c_char, default: i32) -> i32 {
+    unsafe {
+        get(key, default, |x| match x {
+            StoreValue::I32(x) => Some(*x),
+            _ => None,
+        })
+    }
+}
+
+/// # Safety
+/// Only meant to be called by grug
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn game_fn_store_get_f32(key: *const c_char, default: f32) -> f32 {
+    unsafe {
+        get(key, default, |x| match x {
+            StoreValue::F32(x) => Some(*x),
+            _ => None,
+        })
+    }
+}
+
+/// # Safety
+/// Only meant to be called by grug
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn game_fn_store_get_bool(key: *const c_char, default: bool) -> bool {
+    unsafe {
+        get(key, default, |x| match x {
+            StoreValue::Bool(x) => Some(*x),
+            _ => None,
+        })
+    }
+}
+
+/// The returned string lives in the frame arena, so it stays valid until `Grug::end_frame`
+///
+/// # Safety
+/// Only meant to be called by grug
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn game_fn_store_get_string(
+    key: *const c_char,
+    default: *const c_char,
+) -> *const c_char {
+    unsafe {
+        get(key, default, |x| match x {
+            StoreValue::String(x) => Some(alloc_c_str(x)),
+            _ => None,
+        })
+    }
+}
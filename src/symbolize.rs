@@ -0,0 +1,155 @@
+//! Finding out which .grug function a native address is in, for crash handlers
+//!
+//! grug doesn't emit debug info, so there are no line numbers to be had.
+//! It does put every on_function and helper function it compiles in the symbol table of the dll though,
+//! so the function an address is in is the closest symbol before it.
+//! The dynamic linker only matches grug's symbols by their exact address, since grug gives them no size,
+//! so it's only asked which dll an address is in.
+//!
+//! # Example
+//! ```rs
+//! // `address` being an instruction pointer from a signal handler, or a frame of the `backtrace` crate
+//! if let Some(frame) = grug.symbolize_script_address(address) {
+//!     eprintln!("Crashed in {frame}");
+//! }
+//! ```
+
+use std::{
+    ffi::{CStr, c_char, c_int, c_void},
+    fmt,
+    fs::{canonicalize, read},
+    path::{Component, Path, PathBuf},
+};
+
+use crate::Grug;
+
+/// A function of a .grug file that an address is in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptFrame {
+    pub mod_name: String,
+    /// The .grug file the function is defined in
+    pub path: PathBuf,
+    /// The on_function or helper function, without the `_safe` or `_fast` grug gives helper functions
+    pub function: String,
+    /// How many bytes into the function the address is
+    pub offset: usize,
+}
+
+impl fmt::Display for ScriptFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} in {} (+{:#x})",
+            self.function,
+            self.path.display(),
+            self.offset
+        )
+    }
+}
+
+#[repr(C)]
+struct DlInfo {
+    dli_fname: *const c_char,
+    dli_fbase: *mut c_void,
+    dli_sname: *const c_char,
+    dli_saddr: *mut c_void,
+}
+
+unsafe extern "C" {
+    fn dladdr(address: *const c_void, info: *mut DlInfo) -> c_int;
+}
+
+impl Grug {
+    /// The script function `address` is in, or `None` if it isn't in a dll of one of the mods
+    pub fn symbolize_script_address(&self, address: usize) -> Option<ScriptFrame> {
+        let mut info = DlInfo {
+            dli_fname: std::ptr::null(),
+            dli_fbase: std::ptr::null_mut(),
+            dli_sname: std::ptr::null(),
+            dli_saddr: std::ptr::null_mut(),
+        };
+        if unsafe { dladdr(address as *const c_void, &mut info) } == 0 || info.dli_fname.is_null() {
+            return None;
+        }
+
+        let dll_path = unsafe { CStr::from_ptr(info.dli_fname) }.to_string_lossy();
+        let relative_path = self.relative_dll_path(Path::new(&*dll_path))?;
+
+        let Some(Component::Normal(mod_name)) = relative_path.components().next() else {
+            return None;
+        };
+
+        let dll_offset = address.checked_sub(info.dli_fbase as usize)?;
+        let (symbol, symbol_offset) = find_function(&read(&*dll_path).ok()?, dll_offset)?;
+        let function = symbol
+            .strip_suffix("_safe")
+            .or_else(|| symbol.strip_suffix("_fast"))
+            .filter(|_| symbol.starts_with("helper_"))
+            .unwrap_or(&symbol);
+
+        Some(ScriptFrame {
+            mod_name: mod_name.to_string_lossy().to_string(),
+            path: self
+                .source_mods_folder()
+                .join(relative_path.with_extension("grug")),
+            function: function.to_string(),
+            offset: dll_offset - symbol_offset,
+        })
+    }
+
+    /// Where `dll_path` is in the dll folder, if it's in there
+    fn relative_dll_path(&self, dll_path: &Path) -> Option<PathBuf> {
+        if let Ok(path) = dll_path.strip_prefix(&self.mods_dll_folder) {
+            return Some(path.to_path_buf());
+        }
+
+        // grug can be given the dll folder in a different form than the dynamic linker reports it in
+        let dll_path = canonicalize(dll_path).ok()?;
+        let dll_folder = canonicalize(&self.mods_dll_folder).ok()?;
+        dll_path
+            .strip_prefix(dll_folder)
+            .ok()
+            .map(|x| x.to_path_buf())
+    }
+}
+
+const SHT_SYMTAB: u32 = 2;
+const SHF_EXECINSTR: u64 = 4;
+
+/// The name and offset of the function symbol closest before `offset`, in the 64-bit elf file `dll`
+fn find_function(dll: &[u8], offset: usize) -> Option<(String, usize)> {
+    let u16_at =
+        |at: usize| Some(u16::from_le_bytes(dll.get(at..at + 2)?.try_into().ok()?) as usize);
+    let u32_at = |at: usize| Some(u32::from_le_bytes(dll.get(at..at + 4)?.try_into().ok()?));
+    let u64_at = |at: usize| Some(u64::from_le_bytes(dll.get(at..at + 8)?.try_into().ok()?));
+
+    let section_headers = u64_at(0x28)? as usize;
+    let section_header_size = u16_at(0x3a)?;
+    let section_count = u16_at(0x3c)?;
+    let section = |index: usize| section_headers + index * section_header_size;
+    let is_executable = |index: usize| Some(u64_at(section(index) + 8)? & SHF_EXECINSTR != 0);
+
+    let symtab = (0..section_count).find(|&x| u32_at(section(x) + 4) == Some(SHT_SYMTAB))?;
+    let symbols = u64_at(section(symtab) + 24)? as usize;
+    let symbols_size = u64_at(section(symtab) + 32)? as usize;
+    let strtab = u64_at(section(u32_at(section(symtab) + 40)? as usize) + 24)? as usize;
+
+    let mut closest: Option<(usize, usize)> = None;
+    for symbol in (symbols..symbols + symbols_size).step_by(24) {
+        let section_index = u16_at(symbol + 6)?;
+        let value = u64_at(symbol + 8)? as usize;
+        if value > offset
+            || section_index == 0
+            || section_index >= section_count
+            || !is_executable(section_index)?
+            || closest.is_some_and(|(_, closest)| closest > value)
+        {
+            continue;
+        }
+        closest = Some((u32_at(symbol)? as usize, value));
+    }
+
+    let (name, value) = closest?;
+    let name = CStr::from_bytes_until_nul(dll.get(strtab + name..)?).ok()?;
+    Some((name.to_string_lossy().to_string(), value))
+}
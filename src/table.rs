@@ -0,0 +1,232 @@
+//! Read-only tables of data that scripts can look things up in
+//!
+//! The getters scripts call are generated with `grug_table!(items)`, which creates
+//! `items_get_i32(id, column)`, `items_get_f32`, `items_get_bool`, `items_get_string` and `items_has(id)`.
+//! Their `mod_api.json` entries can be added with `ModAPI::add_table`.
+
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::{CStr, CString, c_char},
+    ptr::null,
+    sync::{LazyLock, Mutex},
+};
+
+use crate::{
+    Grug,
+    ffi_string::str_from_ptr,
+    game_function_error,
+    mod_api_type::{Argument, GameFunction, ModAPI},
+};
+
+pub enum TableValue {
+    I32(i32),
+    F32(f32),
+    Bool(bool),
+    String(CString),
+}
+
+impl From<i32> for TableValue {
+    fn from(value: i32) -> Self {
+        Self::I32(value)
+    }
+}
+
+impl From<f32> for TableValue {
+    fn from(value: f32) -> Self {
+        Self::F32(value)
+    }
+}
+
+impl From<bool> for TableValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<&str> for TableValue {
+    fn from(value: &str) -> Self {
+        Self::String(c_string(value))
+    }
+}
+
+impl From<String> for TableValue {
+    fn from(value: String) -> Self {
+        Self::String(c_string(&value))
+    }
+}
+
+fn c_string(value: &str) -> CString {
+    // C strings can't contain a NUL, so it gets dropped
+    CString::new(value.replace('\0', "")).unwrap()
+}
+
+/// A row of a table, keyed by column name
+pub type TableRow = HashMap<String, TableValue>;
+
+/// Every published table, keyed by name and then by row id
+static TABLES: LazyLock<Mutex<HashMap<String, HashMap<i32, TableRow>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Every string `get_string` has returned, which scripts can hold on to for as long as they like
+static STRINGS: LazyLock<Mutex<HashSet<&'static CStr>>> =
+    LazyLock::new(|| Mutex::new(HashSet::new()));
+
+impl Grug {
+    /// Publishes a table for scripts to read from, replacing any table with the same name.
+    ///
+    /// # Example
+    /// ```rs
+    /// grug_table!(items);
+    ///
+    /// grug.publish_table("items", [
+    ///     (1, TableRow::from([
+    ///         ("name".to_string(), "Sword".into()),
+    ///         ("damage".to_string(), 12.into()),
+    ///     ])),
+    /// ]);
+    /// ```
+    pub fn publish_table<S, R>(&self, name: S, rows: R)
+    where
+        S: ToString,
+        R: IntoIterator<Item = (i32, TableRow)>,
+    {
+        TABLES
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), rows.into_iter().collect());
+    }
+}
+
+impl ModAPI {
+    /// Adds the game functions `grug_table!(name)` generates
+    pub fn add_table(&mut self, name: &str) {
+        let getters = [
+            ("i32", "i32"),
+            ("f32", "f32"),
+            ("bool", "bool"),
+            ("string", "string"),
+        ];
+
+        for (suffix, type_) in getters {
+            self.game_functions.insert(
+                format!("{name}_get_{suffix}"),
+                GameFunction {
+                    description: format!("Gets a {type_} from the `{name}` table"),
+                    return_type: Some(type_.to_string()),
+                    arguments: vec![
+                        Argument::new("id", "i32"),
+                        Argument::new("column", "string"),
+                    ],
+                },
+            );
+        }
+
+        self.game_functions.insert(
+            format!("{name}_has"),
+            GameFunction {
+                description: format!("Whether the `{name}` table has a row with this id"),
+                return_type: Some("bool".to_string()),
+                arguments: vec![Argument::new("id", "i32")],
+            },
+        );
+    }
+}
+
+/// Looks up a value, reporting a game function error to grug if it's missing or has the wrong type
+///
+/// # Safety
+/// `column` has to be null or a valid C string
+unsafe fn lookup<T>(
+    table: &str,
+    id: i32,
+    column: *const c_char,
+    fallback: T,
+    get: impl Fn(&TableValue) -> Option<T>,
+) -> T {
+    let column = match unsafe { str_from_ptr(column) } {
+        Ok(column) => column,
+        Err(error) => {
+            game_function_error(&format!("The column of `{table}` {error}"));
+            return fallback;
+        }
+    };
+
+    let tables = TABLES.lock().unwrap();
+    let value = tables
+        .get(table)
+        .and_then(|x| x.get(&id))
+        .and_then(|x| x.get(column));
+
+    let message = match value.map(get) {
+        Some(Some(value)) => return value,
+        Some(None) => format!("`{table}` column `{column}` has a different type"),
+        None => format!("`{table}` has no column `{column}` in row `{id}`"),
+    };
+
+    game_function_error(&message);
+
+    fallback
+}
+
+#[doc(hidden)]
+pub unsafe fn get_i32(table: &str, id: i32, column: *const c_char) -> i32 {
+    unsafe {
+        lookup(table, id, column, 0, |x| match x {
+            TableValue::I32(x) => Some(*x),
+            _ => None,
+        })
+    }
+}
+
+#[doc(hidden)]
+pub unsafe fn get_f32(table: &str, id: i32, column: *const c_char) -> f32 {
+    unsafe {
+        lookup(table, id, column, 0.0, |x| match x {
+            TableValue::F32(x) => Some(*x),
+            _ => None,
+        })
+    }
+}
+
+#[doc(hidden)]
+pub unsafe fn get_bool(table: &str, id: i32, column: *const c_char) -> bool {
+    unsafe {
+        lookup(table, id, column, false, |x| match x {
+            TableValue::Bool(x) => Some(*x),
+            _ => None,
+        })
+    }
+}
+
+/// The returned string stays valid for the rest of the process, even after the table gets published again
+///
+/// Every distinct string gets copied once, so republishing a table only costs memory for strings that changed.
+#[doc(hidden)]
+pub unsafe fn get_string(table: &str, id: i32, column: *const c_char) -> *const c_char {
+    unsafe {
+        lookup(table, id, column, null(), |x| match x {
+            TableValue::String(x) => Some(intern(x).as_ptr()),
+            _ => None,
+        })
+    }
+}
+
+fn intern(value: &CStr) -> &'static CStr {
+    let mut strings = STRINGS.lock().unwrap();
+    if let Some(interned) = strings.get(value) {
+        return interned;
+    }
+
+    let interned = Box::leak(value.to_owned().into_boxed_c_str());
+    strings.insert(interned);
+    interned
+}
+
+#[doc(hidden)]
+pub fn has(table: &str, id: i32) -> bool {
+    TABLES
+        .lock()
+        .unwrap()
+        .get(table)
+        .is_some_and(|x| x.contains_key(&id))
+}
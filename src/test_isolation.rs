@@ -0,0 +1,85 @@
+//! Running grug tests in their own process
+//!
+//! grug keeps all of its state in C globals and can only be initialized once per process,
+//! so two tests creating a `Grug` can't share the test binary.
+//! `isolated` reruns the current test binary for just the one test, and passes the result back.
+//!
+//! Only compiled with the `test-utils` feature, which games can turn on for their dev-dependency on grug-rs.
+
+use std::{
+    env::{current_exe, temp_dir, var},
+    fs::{read_to_string, remove_file, write},
+    process::{Command, exit},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+/// Set in the child process to the name of the test it runs
+const TEST_NAME_VAR: &str = "GRUG_RS_ISOLATED_TEST";
+/// Set in the child process to the file it writes the result to
+const RESULT_PATH_VAR: &str = "GRUG_RS_ISOLATED_RESULT";
+
+static RESULT_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Runs `f` in a child process, and returns what it returned.
+///
+/// `test_name` has to be the name `cargo test` knows the calling test by, since the child
+/// reruns the test binary with only that test. The child exits once `f` returns,
+/// so the rest of the test only runs in the parent.
+/// Panics in the child are turned into a panic here, along with the child's output.
+///
+/// # Example
+/// ```rs
+/// #[test]
+/// fn loads_every_mod() {
+///     let files = isolated("loads_every_mod", || {
+///         let grug = Grug::new(None, "mod_api.json", "mods", "mods_dll", 1000).unwrap();
+///         grug.compile_all_mods().unwrap();
+///         grug.get_files_by_entity_type("Gun").len()
+///     });
+///
+///     assert_eq!(files, 3);
+/// }
+/// ```
+pub fn isolated<T, F>(test_name: &str, f: F) -> T
+where
+    T: Serialize + DeserializeOwned,
+    F: FnOnce() -> T,
+{
+    // We are the child
+    if var(TEST_NAME_VAR).is_ok_and(|x| x == test_name) {
+        let result = f();
+
+        let path = var(RESULT_PATH_VAR).expect("The result path isn't set");
+        write(path, serde_json::to_string(&result).unwrap()).unwrap();
+
+        // The rest of the test only runs in the parent
+        exit(0);
+    }
+
+    let result_path = temp_dir().join(format!(
+        "grug_rs_isolated_{}_{}.json",
+        std::process::id(),
+        RESULT_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let output = Command::new(current_exe().unwrap())
+        .args([test_name, "--exact", "--nocapture", "--test-threads=1"])
+        .env(TEST_NAME_VAR, test_name)
+        .env(RESULT_PATH_VAR, &result_path)
+        .output()
+        .unwrap();
+
+    let result = read_to_string(&result_path);
+    let _ = remove_file(&result_path);
+
+    match result {
+        Ok(json) if output.status.success() => serde_json::from_str(&json).unwrap(),
+        _ => panic!(
+            "Isolated test `{test_name}` failed\n--- stdout\n{}\n--- stderr\n{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        ),
+    }
+}
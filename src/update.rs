@@ -0,0 +1,322 @@
+//! Updating installed mods in place, for in-game mod updaters
+//!
+//! Every update backs up the files it replaces, so it can be undone with `Grug::rollback_mod`.
+
+use std::{
+    ffi::OsString,
+    fs::{copy, create_dir_all, read_to_string, remove_dir_all, remove_file, rename, write},
+    io::ErrorKind,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{Grug, GrugError, walk::collect_files};
+
+/// Added to the name of a file or backup while its new version is being written.
+/// grug ignores it, since it only looks at files ending in `.grug`.
+const STAGING_SUFFIX: &str = ".grug_update";
+
+/// Added to the name of the previous backup while the new one replaces it
+const PREVIOUS_SUFFIX: &str = ".previous";
+
+/// Lives in the dll folder, and holds a folder per mod with the files its last update replaced
+const BACKUPS_FOLDER: &str = "mod_backups";
+
+/// Lists the files the last update added, since rolling back has to delete them
+const ADDED_FILES_NAME: &str = "added.json";
+
+impl Grug {
+    /// Writes `files` into the mod `mod_name` and deletes `removed`, then regenerates the mods.
+    ///
+    /// Paths are relative to the mod folder. Every new file gets written next to the one it replaces first,
+    /// and only once all of them are written do they get renamed over the old ones,
+    /// so a failed write leaves the mod as it was.
+    ///
+    /// The files that get replaced or removed are backed up first, replacing the backup of the previous update.
+    /// If renaming or removing a file fails, or the mods fail to compile afterwards,
+    /// the backup gets restored right away and `GrugError::UpdateRolledBack` is returned.
+    /// If restoring it fails as well, `GrugError::UpdateRollbackFailed` is returned instead,
+    /// and the mod can be left half updated.
+    /// Otherwise only a crash in the middle of renaming can leave the mod half updated, which `rollback_mod` undoes.
+    /// Runtime errors only show up later, so undoing the update after those is up to `rollback_mod`.
+    ///
+    /// Only the files that changed get recompiled.
+    ///
+    /// # Example
+    /// ```rs
+    /// grug.apply_mod_update(
+    ///     "guns",
+    ///     vec![("ak47-Gun.grug", new_ak47_source)],
+    ///     vec!["old_pistol-Gun.grug"],
+    /// )?;
+    /// ```
+    pub fn apply_mod_update<S, P>(
+        &self,
+        mod_name: S,
+        files: Vec<(P, Vec<u8>)>,
+        removed: Vec<P>,
+    ) -> Result<(), GrugError>
+    where
+        S: ToString,
+        P: AsRef<Path>,
+    {
+        let mod_name = mod_name.to_string();
+        let mod_folder = self.source_mods_folder().join(&mod_name);
+
+        if !mod_folder.is_dir() {
+            return Err(GrugError::NotAMod { mod_name });
+        }
+
+        for path in files.iter().map(|(x, _)| x).chain(&removed) {
+            check_mod_path(path.as_ref())?;
+        }
+
+        let mut staged = vec![];
+        for (path, bytes) in files {
+            let target = mod_folder.join(path);
+            let staging = with_suffix(&target, STAGING_SUFFIX);
+
+            let result = target
+                .parent()
+                .map_or(Ok(()), create_dir_all)
+                .and_then(|_| write(&staging, bytes));
+
+            staged.push((staging, target));
+
+            if let Err(error) = result {
+                for (staging, _) in staged {
+                    let _ = remove_file(staging);
+                }
+                return Err(write_error(&mod_folder, error));
+            }
+        }
+
+        let paths: Vec<PathBuf> = staged
+            .iter()
+            .filter_map(|(_, target)| target.strip_prefix(&mod_folder).ok())
+            .chain(removed.iter().map(|x| x.as_ref()))
+            .map(Path::to_path_buf)
+            .collect();
+
+        if let Err(error) = self.back_up(&mod_name, &mod_folder, &paths) {
+            for (staging, _) in staged {
+                let _ = remove_file(staging);
+            }
+            return Err(error);
+        }
+
+        if let Err(error) = swap(&mod_folder, &staged, &removed) {
+            for (staging, _) in staged {
+                let _ = remove_file(staging);
+            }
+            // Puts back the files that were already swapped
+            return Err(self.roll_back_failed_update(mod_name, error));
+        }
+
+        for path in paths {
+            self.invalidate_dll(&mod_name, &path);
+        }
+
+        if let Err(error) = self.regenerate_modified_mods() {
+            return Err(self.roll_back_failed_update(mod_name, error));
+        }
+
+        Ok(())
+    }
+
+    /// Undoes the last `apply_mod_update` of `mod_name`, then regenerates the mods.
+    ///
+    /// Only the last update can be undone, and only once.
+    ///
+    /// # Example
+    /// ```rs
+    /// grug.apply_mod_update("guns", files, removed)?;
+    /// grug.activate_on_function("Gun", "on_spawn", &mut Arguments::empty())?;
+    ///
+    /// if had_runtime_error {
+    ///     grug.rollback_mod("guns")?;
+    /// }
+    /// ```
+    pub fn rollback_mod<S: ToString>(&self, mod_name: S) -> Result<(), GrugError> {
+        let mod_name = mod_name.to_string();
+        let mod_folder = self.source_mods_folder().join(&mod_name);
+        let backup = self.backup_folder(&mod_name);
+
+        let added: Vec<PathBuf> = read_to_string(backup.join(ADDED_FILES_NAME))
+            .ok()
+            .and_then(|x| serde_json::from_str(&x).ok())
+            .ok_or_else(|| GrugError::NoModBackup {
+                mod_name: mod_name.clone(),
+            })?;
+
+        for path in &added {
+            let target = mod_folder.join(path);
+            match remove_file(&target) {
+                Err(error) if error.kind() != ErrorKind::NotFound => {
+                    return Err(write_error(&target, error));
+                }
+                _ => {}
+            }
+            self.invalidate_dll(&mod_name, path);
+        }
+
+        let files_folder = backup.join("files");
+        let mut backed_up = vec![];
+        collect_files(&files_folder, &mut backed_up);
+
+        for source in backed_up {
+            let path = source.strip_prefix(&files_folder).unwrap();
+            let target = mod_folder.join(path);
+
+            target
+                .parent()
+                .map_or(Ok(()), create_dir_all)
+                .and_then(|_| copy(&source, &target))
+                .map_err(|x| write_error(&target, x))?;
+
+            self.invalidate_dll(&mod_name, path);
+        }
+
+        let _ = remove_dir_all(&backup);
+
+        self.regenerate_modified_mods()
+    }
+
+    /// Undoes an update that failed with `error`, and returns the error to report
+    fn roll_back_failed_update(&self, mod_name: String, error: GrugError) -> GrugError {
+        match self.rollback_mod(&mod_name) {
+            Ok(()) => GrugError::UpdateRolledBack {
+                mod_name,
+                error: error.to_string(),
+            },
+            Err(rollback_error) => GrugError::UpdateRollbackFailed {
+                mod_name,
+                error: error.to_string(),
+                rollback_error: rollback_error.to_string(),
+            },
+        }
+    }
+
+    fn backup_folder(&self, mod_name: &str) -> PathBuf {
+        self.mods_dll_folder.join(BACKUPS_FOLDER).join(mod_name)
+    }
+
+    /// Copies the current version of every file in `paths` into the backup of `mod_name`,
+    /// and notes down the ones that don't exist yet
+    ///
+    /// The previous backup only gets replaced once the new one is complete.
+    fn back_up(
+        &self,
+        mod_name: &str,
+        mod_folder: &Path,
+        paths: &[PathBuf],
+    ) -> Result<(), GrugError> {
+        let backup = self.backup_folder(mod_name);
+        let staging = with_suffix(&backup, STAGING_SUFFIX);
+        let _ = remove_dir_all(&staging);
+
+        if let Err(error) = write_backup(&staging, mod_folder, paths) {
+            let _ = remove_dir_all(&staging);
+            return Err(error);
+        }
+
+        let previous = with_suffix(&backup, PREVIOUS_SUFFIX);
+        let _ = remove_dir_all(&previous);
+        let _ = rename(&backup, &previous);
+
+        if let Err(error) = rename(&staging, &backup) {
+            let _ = rename(&previous, &backup);
+            let _ = remove_dir_all(&staging);
+            return Err(write_error(&backup, error));
+        }
+        let _ = remove_dir_all(&previous);
+
+        Ok(())
+    }
+
+    /// Deletes the dll of a changed file, so it gets recompiled.
+    /// grug compares modification times in whole seconds, which misses a file changed within the second its dll was written.
+    fn invalidate_dll(&self, mod_name: &str, path: &Path) {
+        if path.extension().is_some_and(|x| x == "grug") {
+            let dll_path = self.mods_dll_folder.join(mod_name).join(path);
+            let _ = remove_file(dll_path.with_extension("so"));
+        }
+    }
+}
+
+/// Renames every staged file over the file it replaces, then deletes `removed`
+fn swap<P: AsRef<Path>>(
+    mod_folder: &Path,
+    staged: &[(PathBuf, PathBuf)],
+    removed: &[P],
+) -> Result<(), GrugError> {
+    for (staging, target) in staged {
+        rename(staging, target).map_err(|x| write_error(target, x))?;
+    }
+
+    for path in removed {
+        let target = mod_folder.join(path);
+        match remove_file(&target) {
+            Err(error) if error.kind() != ErrorKind::NotFound => {
+                return Err(write_error(&target, error));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes a backup of the files in `paths` to `backup`
+fn write_backup(backup: &Path, mod_folder: &Path, paths: &[PathBuf]) -> Result<(), GrugError> {
+    let files_folder = backup.join("files");
+    let mut added = vec![];
+
+    for path in paths {
+        let source = mod_folder.join(path);
+        if !source.is_file() {
+            added.push(path.to_path_buf());
+            continue;
+        }
+
+        let target = files_folder.join(path);
+        target
+            .parent()
+            .map_or(Ok(()), create_dir_all)
+            .and_then(|_| copy(&source, &target))
+            .map_err(|x| write_error(&target, x))?;
+    }
+
+    let added_path = backup.join(ADDED_FILES_NAME);
+    create_dir_all(backup)
+        .and_then(|_| write(&added_path, serde_json::to_string(&added).unwrap()))
+        .map_err(|x| write_error(&added_path, x))
+}
+
+/// Makes sure `path` can't point outside of the mod folder
+fn check_mod_path(path: &Path) -> Result<(), GrugError> {
+    let is_inside = path
+        .components()
+        .all(|x| matches!(x, Component::Normal(_) | Component::CurDir));
+
+    if !is_inside || path.as_os_str().is_empty() {
+        return Err(GrugError::InvalidModPath {
+            path: path.to_path_buf(),
+        });
+    }
+
+    Ok(())
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = OsString::from(path.as_os_str());
+    path.push(suffix);
+
+    PathBuf::from(path)
+}
+
+fn write_error(path: &Path, error: std::io::Error) -> GrugError {
+    GrugError::WriteModFile {
+        path: path.to_path_buf(),
+        error: error.to_string(),
+    }
+}
@@ -0,0 +1,30 @@
+//! Walking mod folders, skipping anything that can't be read
+
+use std::{
+    fs::read_dir,
+    path::{Path, PathBuf},
+};
+
+/// Recursively collects every file in `dir`
+pub(crate) fn collect_files(dir: &Path, paths: &mut Vec<PathBuf>) {
+    for entry in read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(&path, paths);
+        } else {
+            paths.push(path);
+        }
+    }
+}
+
+/// Recursively collects every `.grug` file in `dir`
+pub(crate) fn collect_grug_files(dir: &Path, paths: &mut Vec<PathBuf>) {
+    let mut files = vec![];
+    collect_files(dir, &mut files);
+
+    paths.extend(
+        files
+            .into_iter()
+            .filter(|x| x.extension().is_some_and(|x| x == "grug")),
+    );
+}
@@ -0,0 +1,106 @@
+use std::{
+    env::temp_dir,
+    fs::{copy, create_dir_all, read_dir, remove_dir_all},
+    process,
+    sync::{Arc, Mutex},
+};
+
+use grug_rs::{Arguments, Grug, GrugError, dynamic::call, test_isolation::isolated};
+use grug_rs_proc_macro::game_function;
+
+static LOG: Mutex<Vec<i32>> = Mutex::new(vec![]);
+
+#[game_function]
+fn add(x: i32) -> i32 {
+    call("add", (x,))
+}
+
+#[game_function]
+fn add_f32(x: f32) -> i32 {
+    call("add", (x,))
+}
+
+#[game_function]
+fn log_int(value: i32) {
+    LOG.lock().unwrap().push(value);
+}
+
+/// What the on_function logged, or `None` when it hit a runtime error before logging
+fn run(grug: &Grug, on_function: &str) -> Option<i32> {
+    grug.activate_on_function("Adder", on_function, &mut Arguments::empty())
+        .unwrap();
+    LOG.lock().unwrap().pop()
+}
+
+#[test]
+fn registered_closures_can_be_replaced_and_unregistered() {
+    let results = isolated(
+        "registered_closures_can_be_replaced_and_unregistered",
+        || {
+            let folder =
+                temp_dir().join(format!("grug_rs_dynamic_game_functions_{}", process::id()));
+            let _ = remove_dir_all(&folder);
+            create_dir_all(folder.join("mods/base")).unwrap();
+            for entry in read_dir("./tests/dynamic_game_functions/mods/base").unwrap() {
+                let path = entry.unwrap().path();
+                copy(
+                    &path,
+                    folder.join("mods/base").join(path.file_name().unwrap()),
+                )
+                .unwrap();
+            }
+
+            let grug = Grug::new(
+                None,
+                "./tests/dynamic_game_functions/mod_api.json",
+                folder.join("mods"),
+                folder.join("mods_dll"),
+                1000,
+            )
+            .unwrap();
+
+            let mut results = vec![];
+            let world = Arc::new(10);
+
+            let captured = world.clone();
+            grug.register_game_function("add", move |(x,): (i32,)| *captured + x)
+                .unwrap();
+            results.push(run(&grug, "on_add"));
+
+            // Registering twice is an error and keeps the original
+            let result = grug.register_game_function("add", |(x,): (i32,)| x);
+            assert!(matches!(
+                result,
+                Err(GrugError::GameFunctionAlreadyRegistered { .. })
+            ));
+            results.push(run(&grug, "on_add"));
+
+            // Replacing drops the old closure along with its capture
+            assert!(grug.replace_game_function("add", |(x,): (i32,)| x * 2));
+            assert_eq!(Arc::strong_count(&world), 1);
+            results.push(run(&grug, "on_add"));
+
+            // Different argument types are a runtime error
+            results.push(run(&grug, "on_add_f32"));
+
+            // Unregistering removes it, after which calls are runtime errors
+            assert!(grug.unregister_game_function("add"));
+            assert!(!grug.unregister_game_function("add"));
+            results.push(run(&grug, "on_add"));
+
+            // Replacing something that isn't registered just registers it
+            assert!(!grug.replace_game_function("add", |(x,): (i32,)| x + 1));
+            results.push(run(&grug, "on_add"));
+
+            let errors = grug.snapshot().recent_errors.len();
+            let _ = remove_dir_all(&folder);
+
+            (results, errors)
+        },
+    );
+
+    assert_eq!(
+        results,
+        (vec![Some(15), Some(15), Some(10), None, None, Some(6)], 2)
+    );
+}
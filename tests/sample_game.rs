@@ -0,0 +1,1115 @@
+use std::{
+    env::temp_dir,
+    fs::{File, copy, create_dir_all, read_dir, read_to_string, remove_dir_all, write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use grug_rs::{
+    Arguments, CustomValue, Grug, GrugError, GrugParam, GrugValue,
+    dispatch::FileStatus,
+    file_toggles::FileId,
+    headless::QuarantinePolicy,
+    migrate::{self, Rename},
+    mod_api_type::Entity,
+    profiles::Profile,
+    table::TableRow,
+    test_isolation::isolated,
+};
+use grug_rs_proc_macro::{GrugStruct, game_function, grug_table};
+use linked_hash_map::LinkedHashMap;
+
+#[cfg(feature = "cache")]
+use grug_rs::cache::StaleReason;
+#[cfg(any(feature = "commands", feature = "settings"))]
+use grug_rs::mod_api_type::ModAPI;
+#[cfg(feature = "commands")]
+use grug_rs::stdlib::commands::{CommandBuffer, DeserializeFromArgs};
+#[cfg(feature = "settings")]
+use grug_rs::stdlib::settings::SettingValue;
+
+// Every test runs in its own process, since each of them initializes grug
+
+static LOG: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+#[repr(C)]
+#[derive(Debug, Clone)]
+struct Health {
+    value: i32,
+}
+
+#[game_function]
+fn log(msg: String) {
+    LOG.lock().unwrap().push(msg.to_string());
+}
+
+#[game_function]
+fn log_int(value: i32) {
+    LOG.lock().unwrap().push(value.to_string());
+}
+
+#[game_function]
+fn shout(msg: String) -> String {
+    msg.to_uppercase()
+}
+
+#[game_function]
+fn hurt(target: &mut Health, amount: i32) {
+    target.value -= amount;
+}
+
+#[derive(GrugStruct, Clone, Copy)]
+#[repr(C)]
+struct Position {
+    x: i32,
+    y: i32,
+}
+
+#[game_function]
+fn log_position(position: Position) {
+    LOG.lock()
+        .unwrap()
+        .push(format!("{},{}", position.x, position.y));
+}
+
+struct Gold(i32);
+
+impl GrugParam for Gold {
+    type Raw = i32;
+    const GRUG_TYPE: &'static str = "i32";
+
+    unsafe fn from_grug(raw: i32) -> Self {
+        Self(raw)
+    }
+}
+
+#[game_function]
+fn log_gold(amount: Gold) {
+    LOG.lock().unwrap().push(format!("{} gold", amount.0));
+}
+
+grug_table!(items);
+
+fn take_log() -> Vec<String> {
+    std::mem::take(&mut *LOG.lock().unwrap())
+}
+
+fn copy_dir(from: &Path, to: &Path) {
+    create_dir_all(to).unwrap();
+    for entry in read_dir(from).unwrap() {
+        let path = entry.unwrap().path();
+        let target = to.join(path.file_name().unwrap());
+        if path.is_dir() {
+            copy_dir(&path, &target);
+        } else {
+            copy(&path, &target).unwrap();
+        }
+    }
+}
+
+/// A fresh copy of the sample game, so tests can rewrite its files
+fn sample_game(name: &str) -> PathBuf {
+    let folder = temp_dir().join(format!("grug_rs_sample_game_{name}"));
+    let _ = remove_dir_all(&folder);
+    copy_dir(Path::new("./tests/sample_game/mods"), &folder.join("mods"));
+    folder
+}
+
+fn init_grug(name: &str) -> (Grug, PathBuf) {
+    let folder = sample_game(name);
+    let grug = Grug::new(
+        None,
+        "./tests/sample_game/mod_api.json",
+        folder.join("mods"),
+        folder.join("mods_dll"),
+        1000,
+    )
+    .unwrap();
+    (grug, folder.join("mods"))
+}
+
+/// grug only notices changes that are newer than the dll, to the second
+fn rewrite(path: &Path, source: &str) {
+    write(path, source).unwrap();
+    File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(SystemTime::now() + Duration::from_secs(2))
+        .unwrap();
+}
+
+/// Like `init_grug`, after letting `edit` add the game functions of stdlib packs to the mod API
+#[cfg(any(feature = "commands", feature = "settings"))]
+fn init_grug_with(name: &str, edit: impl FnOnce(&mut ModAPI)) -> (Grug, PathBuf) {
+    let folder = sample_game(name);
+
+    let json = read_to_string("./tests/sample_game/mod_api.json").unwrap();
+    let mut mod_api: ModAPI = serde_json::from_str(&json).unwrap();
+    edit(&mut mod_api);
+    let mod_api_path = folder.join("mod_api.json");
+    write(&mod_api_path, serde_json::to_string(&mod_api).unwrap()).unwrap();
+
+    let grug = Grug::new(
+        None,
+        mod_api_path,
+        folder.join("mods"),
+        folder.join("mods_dll"),
+        1000,
+    )
+    .unwrap();
+    (grug, folder.join("mods"))
+}
+
+fn sorted(mut log: Vec<String>) -> Vec<String> {
+    log.sort();
+    log
+}
+
+#[test]
+fn grug_can_be_shared_between_threads() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Grug>();
+}
+
+#[test]
+fn entities_run_their_own_files() {
+    let (spawned, ticked) = isolated("entities_run_their_own_files", || {
+        let (grug, _) = init_grug("entities");
+
+        let mut args = Arguments::new(vec![GrugValue::String("Alice".to_string())]);
+        grug.activate_on_function("Player", "on_spawn", &mut args)
+            .unwrap();
+        let spawned = take_log();
+
+        grug.activate_on_function("Enemy", "on_tick", &mut Arguments::empty())
+            .unwrap();
+        (spawned, sorted(take_log()))
+    });
+
+    assert_eq!(spawned, ["Alice"]);
+    assert_eq!(ticked, ["goblin", "orc"]);
+}
+
+#[test]
+fn ints_and_custom_values_reach_game_functions() {
+    let (damage, health) = isolated("ints_and_custom_values_reach_game_functions", || {
+        let (grug, _) = init_grug("arguments");
+
+        let mut args = Arguments::new(vec![GrugValue::I32(4)]);
+        grug.activate_on_function("Player", "on_damage", &mut args)
+            .unwrap();
+
+        let mut health = Health { value: 20 };
+        let mut args = Arguments::new(vec![GrugValue::custom(&mut health)]);
+        grug.activate_on_function("Enemy", "on_attack", &mut args)
+            .unwrap();
+        drop(args);
+
+        (take_log(), health.value)
+    });
+
+    assert_eq!(damage, ["25"]);
+    assert_eq!(health, 15);
+}
+
+#[test]
+fn rewritten_files_get_reloaded() {
+    let (before, after) = isolated("rewritten_files_get_reloaded", || {
+        let (grug, mods) = init_grug("reload");
+
+        grug.activate_on_function("Enemy", "on_tick", &mut Arguments::empty())
+            .unwrap();
+        let before = sorted(take_log());
+
+        rewrite(
+            &mods.join("base/goblin-Enemy.grug"),
+            "on_tick() {\n    log(\"hobgoblin\")\n}\n",
+        );
+        grug.activate_on_function("Enemy", "on_tick", &mut Arguments::empty())
+            .unwrap();
+        (before, sorted(take_log()))
+    });
+
+    assert_eq!(before, ["goblin", "orc"]);
+    assert_eq!(after, ["hobgoblin", "orc"]);
+}
+
+#[test]
+fn broken_files_fail_to_load_until_fixed() {
+    let (broken, fixed, log) = isolated("broken_files_fail_to_load_until_fixed", || {
+        let (grug, mods) = init_grug("broken");
+        grug.regenerate_modified_mods().unwrap();
+
+        let path = mods.join("extra/orc-Enemy.grug");
+        rewrite(&path, "on_tick() {\n    log(\"orc\"\n}\n");
+        let broken = matches!(
+            grug.regenerate_modified_mods(),
+            Err(GrugError::FileLoading { .. })
+        );
+
+        rewrite(&path, "on_tick() {\n    log(\"fixed orc\")\n}\n");
+        let fixed = grug.regenerate_modified_mods().is_ok();
+
+        grug.activate_on_function("Enemy", "on_tick", &mut Arguments::empty())
+            .unwrap();
+        (broken, fixed, sorted(take_log()))
+    });
+
+    assert!(broken);
+    assert!(fixed);
+    assert_eq!(log, ["fixed orc", "goblin"]);
+}
+
+#[test]
+fn unknown_entities_and_on_functions_are_errors() {
+    let (entity, on_function) = isolated("unknown_entities_and_on_functions_are_errors", || {
+        let (grug, _) = init_grug("unknown");
+
+        let entity = matches!(
+            grug.activate_on_function("Dragon", "on_tick", &mut Arguments::empty()),
+            Err(GrugError::NotAnEntity { .. })
+        );
+        let on_function = matches!(
+            grug.activate_on_function("Enemy", "on_fly", &mut Arguments::empty()),
+            Err(GrugError::NotAnOnFunction { .. })
+        );
+        (entity, on_function)
+    });
+
+    assert!(entity);
+    assert!(on_function);
+}
+
+#[test]
+fn f32_arguments_are_errors() {
+    let (rejected, log) = isolated("f32_arguments_are_errors", || {
+        let (grug, _) = init_grug("f32_arguments");
+
+        let mut args = Arguments::new(vec![GrugValue::F32(4.0)]);
+        let rejected = matches!(
+            grug.activate_on_function("Player", "on_damage", &mut args),
+            Err(GrugError::UnsupportedF32Argument { .. })
+        );
+        (rejected, take_log())
+    });
+
+    assert!(rejected);
+    assert!(log.is_empty());
+}
+
+#[test]
+fn runtime_errors_show_up_in_snapshots() {
+    let (log, errors) = isolated("runtime_errors_show_up_in_snapshots", || {
+        let (grug, _) = init_grug("runtime_errors");
+
+        let mut args = Arguments::new(vec![GrugValue::I32(0)]);
+        let _ = grug.activate_on_function("Player", "on_damage", &mut args);
+
+        let snapshot = grug.snapshot();
+        let errors: Vec<_> = snapshot
+            .recent_errors
+            .iter()
+            .map(|x| (format!("{:?}", x.kind), x.on_function.clone()))
+            .collect();
+        (take_log(), errors)
+    });
+
+    assert!(log.is_empty());
+    assert_eq!(
+        errors,
+        [("DivisionByZero".to_string(), "on_damage".to_string())]
+    );
+}
+
+#[test]
+fn paused_grug_runs_nothing() {
+    let (paused, resumed) = isolated("paused_grug_runs_nothing", || {
+        let (grug, _) = init_grug("pause");
+
+        grug.pause();
+        grug.activate_on_function("Enemy", "on_tick", &mut Arguments::empty())
+            .unwrap();
+        grug.broadcast_all_entities("on_tick", &mut Arguments::empty())
+            .unwrap();
+        let paused = take_log();
+
+        grug.resume();
+        grug.activate_on_function("Enemy", "on_tick", &mut Arguments::empty())
+            .unwrap();
+        (paused, sorted(take_log()))
+    });
+
+    assert!(paused.is_empty());
+    assert_eq!(resumed, ["goblin", "orc"]);
+}
+
+#[test]
+fn broadcasts_reach_every_entity_type() {
+    let log = isolated("broadcasts_reach_every_entity_type", || {
+        let (grug, _) = init_grug("broadcast");
+
+        grug.broadcast_all_entities("on_tick", &mut Arguments::empty())
+            .unwrap();
+        sorted(take_log())
+    });
+
+    assert_eq!(log, ["goblin", "orc"]);
+}
+
+#[test]
+fn profiles_pick_mods_and_their_order() {
+    let (reversed, base_only) = isolated("profiles_pick_mods_and_their_order", || {
+        let folder = sample_game("profiles");
+        let grug = Grug::new_switchable(
+            None,
+            "./tests/sample_game/mod_api.json",
+            folder.join("mods"),
+            folder.join("mods_dll"),
+            1000,
+        )
+        .unwrap();
+
+        grug.apply_profile(&Profile::new(["extra", "base"]))
+            .unwrap();
+        grug.activate_on_function("Enemy", "on_tick", &mut Arguments::empty())
+            .unwrap();
+        let reversed = take_log();
+
+        grug.apply_profile(&Profile::new(["base"])).unwrap();
+        grug.activate_on_function("Enemy", "on_tick", &mut Arguments::empty())
+            .unwrap();
+        (reversed, take_log())
+    });
+
+    assert_eq!(reversed, ["orc", "goblin"]);
+    assert_eq!(base_only, ["goblin"]);
+}
+
+#[test]
+fn crash_reports_list_mods_and_recent_calls() {
+    let report = isolated("crash_reports_list_mods_and_recent_calls", || {
+        let (grug, _) = init_grug("crash_report");
+
+        let mut args = Arguments::new(vec![GrugValue::String("Bob".to_string())]);
+        grug.activate_on_function("Player", "on_spawn", &mut args)
+            .unwrap();
+        grug.crash_report_blob()
+    });
+
+    let report: serde_json::Value = serde_json::from_str(&report).unwrap();
+
+    // The dlls only depend on the grug that compiled them
+    assert_eq!(report["backend"], grug_rs::backend_version());
+    assert!(grug_rs::backend_version().starts_with("grug-sys "));
+
+    let mut mods: Vec<_> = report["mods"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|x| x["name"].as_str().unwrap())
+        .collect();
+    mods.sort();
+    assert_eq!(mods, ["base", "extra"]);
+
+    let calls = report["recent_calls"].as_array().unwrap();
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0]["mod"], "base");
+    assert_eq!(calls[0]["on_function"], "on_spawn");
+}
+
+#[test]
+fn addresses_in_dlls_name_their_script_function() {
+    let (frame, host) = isolated("addresses_in_dlls_name_their_script_function", || {
+        let (grug, mods) = init_grug("symbolize");
+        grug.regenerate_modified_mods().unwrap();
+
+        let files = grug.get_files_by_entity_type("Enemy");
+        let goblin = files
+            .iter()
+            .find(|x| unsafe { std::ffi::CStr::from_ptr(x.inner.name) } == c"goblin-Enemy.grug")
+            .unwrap();
+
+        // on_attack comes second in the mod API
+        let on_attack = unsafe { *(goblin.inner.on_fns as *const usize).add(1) };
+        let frame = grug.symbolize_script_address(on_attack + 4).unwrap();
+        assert_eq!(frame.path, mods.join("base/goblin-Enemy.grug"));
+
+        let host = grug.symbolize_script_address(take_log as *const () as usize);
+        (
+            (frame.mod_name, frame.function, frame.offset),
+            host.is_none(),
+        )
+    });
+
+    assert_eq!(frame, ("base".to_string(), "on_attack".to_string(), 4));
+    assert!(host);
+}
+
+#[test]
+fn disabled_files_get_skipped() {
+    let (disabled, enabled, missing) = isolated("disabled_files_get_skipped", || {
+        let (grug, _) = init_grug("file_toggles");
+
+        let goblin = FileId::new("base", "goblin-Enemy.grug");
+        grug.set_file_enabled(&goblin, false).unwrap();
+        grug.broadcast_all_entities("on_tick", &mut Arguments::empty())
+            .unwrap();
+        let disabled = take_log();
+
+        grug.set_file_enabled(&goblin, true).unwrap();
+        grug.activate_on_function("Enemy", "on_tick", &mut Arguments::empty())
+            .unwrap();
+
+        let missing = matches!(
+            grug.set_file_enabled(&FileId::new("base", "dragon-Enemy.grug"), false),
+            Err(GrugError::NotAModFile { .. })
+        );
+        (disabled, sorted(take_log()), missing)
+    });
+
+    assert_eq!(disabled, ["orc"]);
+    assert_eq!(enabled, ["goblin", "orc"]);
+    assert!(missing);
+}
+
+#[test]
+fn batches_call_once_per_item() {
+    let (log, health) = isolated("batches_call_once_per_item", || {
+        let (grug, _) = init_grug("batch");
+
+        grug.activate_for_each(
+            "Player",
+            "on_spawn",
+            ["Alice", "Bob", "Alice"].map(|x| vec![GrugValue::String(x.to_string())]),
+        )
+        .unwrap();
+
+        let mut targets = [Health { value: 20 }, Health { value: 10 }];
+        grug.activate_for_each(
+            "Enemy",
+            "on_attack",
+            targets.iter_mut().map(|x| vec![GrugValue::custom(x)]),
+        )
+        .unwrap();
+
+        (take_log(), targets.map(|x| x.value))
+    });
+
+    assert_eq!(log, ["Alice", "Bob", "Alice"]);
+    assert_eq!(health, [15, 5]);
+}
+
+#[test]
+fn detailed_dispatches_report_every_file() {
+    let statuses = isolated("detailed_dispatches_report_every_file", || {
+        let (grug, mods) = init_grug("dispatch");
+        rewrite(
+            &mods.join("extra/orc-Enemy.grug"),
+            "on_attack(target: Health) {\n    log_int(1 / 0)\n}\n",
+        );
+        grug.set_file_enabled(&FileId::new("base", "goblin-Enemy.grug"), false)
+            .unwrap();
+
+        let mut health = Health { value: 20 };
+        let mut args = Arguments::new(vec![GrugValue::custom(&mut health)]);
+        let outcome = grug
+            .activate_on_function_detailed("Enemy", "on_attack", &mut args)
+            .unwrap();
+
+        let mut statuses: Vec<_> = outcome
+            .files
+            .iter()
+            .map(|x| {
+                let status = match &x.status {
+                    FileStatus::Errored(error) => format!("errored in {}", error.on_function),
+                    status => format!("{status:?}"),
+                };
+                (x.file.clone(), status, x.duration.is_some())
+            })
+            .collect();
+        statuses.sort();
+        (statuses, outcome.is_ok())
+    });
+
+    assert_eq!(
+        statuses,
+        (
+            vec![
+                (
+                    "goblin-Enemy.grug".to_string(),
+                    "Disabled".to_string(),
+                    false
+                ),
+                (
+                    "orc-Enemy.grug".to_string(),
+                    "errored in on_attack".to_string(),
+                    // Which doesn't get timed in shipping builds
+                    !cfg!(feature = "release-unchecked")
+                ),
+            ],
+            false
+        )
+    );
+}
+
+#[test]
+fn returned_strings_live_until_the_end_of_the_frame() {
+    let (log, capacities) = isolated("returned_strings_live_until_the_end_of_the_frame", || {
+        let (grug, _) = init_grug("frame");
+
+        let mut capacities = vec![];
+        for name in ["Alice", "Bob"] {
+            let mut args = Arguments::new(vec![GrugValue::String(name.to_string())]);
+            grug.activate_on_function("Player", "on_greet", &mut args)
+                .unwrap();
+            grug.end_frame();
+            capacities.push(grug.frame_arena_capacity());
+        }
+        (take_log(), capacities)
+    });
+
+    assert_eq!(log, ["ALICE", "BOB"]);
+    // The chunk of the first frame gets reused by the second
+    assert_eq!(capacities[0], capacities[1]);
+}
+
+#[test]
+fn mods_reload_only_when_asked_to() {
+    let (before, after) = isolated("mods_reload_only_when_asked_to", || {
+        let (grug, mods) = init_grug("manual_reload");
+        grug.set_auto_regenerate(false);
+
+        // Loads the mods
+        grug.activate_on_function("Enemy", "on_tick", &mut Arguments::empty())
+            .unwrap();
+        take_log();
+
+        rewrite(
+            &mods.join("extra/orc-Enemy.grug"),
+            "on_tick() {\n    log(\"uruk\")\n}\n",
+        );
+        grug.activate_on_function("Enemy", "on_tick", &mut Arguments::empty())
+            .unwrap();
+        let before = sorted(take_log());
+
+        assert!(!grug.take_reload_request());
+        grug.request_reload();
+        if grug.take_reload_request() {
+            grug.regenerate_modified_mods().unwrap();
+        }
+        grug.activate_on_function("Enemy", "on_tick", &mut Arguments::empty())
+            .unwrap();
+        (before, sorted(take_log()))
+    });
+
+    assert_eq!(before, ["goblin", "orc"]);
+    assert_eq!(after, ["goblin", "uruk"]);
+}
+
+#[test]
+fn files_that_keep_erroring_get_quarantined() {
+    let (quarantined, statuses, released) =
+        isolated("files_that_keep_erroring_get_quarantined", || {
+            let (grug, _) = init_grug("quarantine");
+            grug.set_quarantine_policy(Some(QuarantinePolicy {
+                max_runtime_errors: 2,
+            }));
+
+            let mut statuses = vec![];
+            for _ in 0..3 {
+                let mut args = Arguments::new(vec![GrugValue::I32(0)]);
+                let outcome = grug
+                    .activate_on_function_detailed("Player", "on_damage", &mut args)
+                    .unwrap();
+                statuses.push(matches!(outcome.files[0].status, FileStatus::Disabled));
+            }
+            let quarantined: Vec<_> = grug
+                .quarantined_files()
+                .into_iter()
+                .map(|x| format!("{}/{}", x.mod_name, x.path))
+                .collect();
+
+            grug.release_quarantined_files();
+            let knight = FileId::new("base", "knight-Player.grug");
+            (quarantined, statuses, grug.is_file_enabled(&knight))
+        });
+
+    assert_eq!(quarantined, ["base/knight-Player.grug"]);
+    assert_eq!(statuses, [false, false, true]);
+    assert!(released);
+}
+
+#[test]
+fn helper_functions_can_be_called_directly() {
+    let (helper_functions, armor, errors) =
+        isolated("helper_functions_can_be_called_directly", || {
+            let (grug, _) = init_grug("helpers");
+            let knight = FileId::new("base", "knight-Player.grug");
+
+            let helper_functions: Vec<_> = grug
+                .helper_functions(&knight)
+                .unwrap()
+                .into_iter()
+                .map(|x| x.name)
+                .collect();
+
+            let mut args = Arguments::new(vec![GrugValue::I32(10)]);
+            let armor: i32 = grug
+                .call_helper_function(&knight, "helper_armor", &mut args)
+                .unwrap();
+
+            let mut args = Arguments::new(vec![GrugValue::String("10".to_string())]);
+            let wrong_type =
+                grug.call_helper_function::<i32, _>(&knight, "helper_armor", &mut args);
+            let missing = grug.call_helper_function::<i32, _>(&knight, "helper_missing", &mut args);
+            let errors = [wrong_type, missing].map(|x| x.unwrap_err().to_string());
+
+            (helper_functions, armor, errors)
+        });
+
+    assert_eq!(helper_functions, ["helper_armor"]);
+    assert_eq!(armor, 5);
+    assert_eq!(
+        errors,
+        [
+            "`helper_armor` is `(i32) -> i32`, not `(string) -> i32`",
+            "`helper_missing` is not a helper function of `knight-Player.grug`",
+        ]
+    );
+}
+
+#[test]
+fn migrations_rename_installed_files() {
+    let mods = sample_game("migrate").join("mods");
+    let on_update = Rename::OnFunction {
+        entity: "Enemy".to_string(),
+        old: "on_tick".to_string(),
+        new: "on_update".to_string(),
+    };
+    let hero = Rename::Entity {
+        old: "Player".to_string(),
+        new: "Hero".to_string(),
+    };
+
+    let planned = migrate::dry_run(&mods, std::slice::from_ref(&on_update)).unwrap();
+    let planned: Vec<_> = planned
+        .files
+        .iter()
+        .map(|x| {
+            (
+                x.mod_name.as_str(),
+                x.renamed_on_functions,
+                x.backup.is_some(),
+            )
+        })
+        .collect();
+    assert_eq!(planned, [("base", 1, false), ("extra", 1, false)]);
+    let orc = read_to_string(mods.join("extra/orc-Enemy.grug")).unwrap();
+    assert!(orc.starts_with("on_tick()"));
+
+    let migrated = migrate::rename_all(&mods, &[on_update, hero]).unwrap();
+    let new_paths: Vec<_> = migrated.files.iter().map(|x| &x.new_path).collect();
+    assert_eq!(
+        new_paths,
+        [
+            Path::new("goblin-Enemy.grug"),
+            Path::new("knight-Hero.grug"),
+            Path::new("orc-Enemy.grug"),
+        ]
+    );
+
+    let orc = read_to_string(mods.join("extra/orc-Enemy.grug")).unwrap();
+    assert_eq!(orc, "on_update() {\n    log(\"orc\")\n}\n");
+    assert!(mods.join("extra/orc-Enemy.grug.bak").is_file());
+    assert!(!mods.join("base/knight-Player.grug").exists());
+    assert!(mods.join("base/knight-Player.grug.bak").is_file());
+    assert!(mods.join("base/knight-Hero.grug").is_file());
+
+    // Renaming back onto a file that exists leaves everything as it is
+    let villain = Rename::Entity {
+        old: "Hero".to_string(),
+        new: "Enemy".to_string(),
+    };
+    copy(
+        mods.join("base/knight-Hero.grug"),
+        mods.join("base/knight-Enemy.grug"),
+    )
+    .unwrap();
+    assert!(matches!(
+        migrate::rename(&mods, villain),
+        Err(GrugError::MigrationConflict { .. })
+    ));
+    assert!(mods.join("base/knight-Hero.grug").is_file());
+}
+
+#[test]
+fn migrations_never_lose_files() {
+    let mods = sample_game("migrate_conflicts").join("mods");
+    let goblin = read_to_string(mods.join("base/goblin-Enemy.grug")).unwrap();
+
+    // Two files renamed to the same file
+    write(mods.join("base/ak47-Gun.grug"), "").unwrap();
+    write(mods.join("base/ak47-Rifle.grug"), "").unwrap();
+    let weapon = |old: &str| Rename::Entity {
+        old: old.to_string(),
+        new: "Weapon".to_string(),
+    };
+    assert!(matches!(
+        migrate::rename_all(&mods, &[weapon("Gun"), weapon("Rifle")]),
+        Err(GrugError::MigrationConflict { .. })
+    ));
+    assert!(mods.join("base/ak47-Gun.grug").is_file());
+    assert!(mods.join("base/ak47-Rifle.grug").is_file());
+    assert!(!mods.join("base/ak47-Weapon.grug").exists());
+
+    // The backup of a file migrated twice keeps the original
+    let rename_on_tick = |old: &str, new: &str| Rename::OnFunction {
+        entity: "Enemy".to_string(),
+        old: old.to_string(),
+        new: new.to_string(),
+    };
+    migrate::rename(&mods, rename_on_tick("on_tick", "on_update")).unwrap();
+    migrate::rename(&mods, rename_on_tick("on_update", "on_step")).unwrap();
+    let backup = read_to_string(mods.join("base/goblin-Enemy.grug.bak")).unwrap();
+    assert_eq!(backup, goblin);
+
+    // The entity type starts after the first dash, like grug has it
+    write(mods.join("base/fire-ball-Spell.grug"), "").unwrap();
+    let spell = Rename::Entity {
+        old: "Spell".to_string(),
+        new: "Magic".to_string(),
+    };
+    let planned = migrate::dry_run(&mods, &[spell]).unwrap();
+    assert!(planned.files.is_empty());
+}
+
+// `release-unchecked` compiles out the timing
+#[cfg(not(feature = "release-unchecked"))]
+#[test]
+fn perf_recordings_tell_mods_apart() {
+    let files = isolated("perf_recordings_tell_mods_apart", || {
+        let (grug, mods) = init_grug("perf");
+        copy(
+            mods.join("base/goblin-Enemy.grug"),
+            mods.join("extra/goblin-Enemy.grug"),
+        )
+        .unwrap();
+        grug.regenerate_modified_mods().unwrap();
+
+        grug.begin_perf_recording();
+        grug.activate_on_function("Enemy", "on_tick", &mut Arguments::empty())
+            .unwrap();
+        take_log();
+        let timings = grug.end_perf_recording();
+
+        timings.timings.into_keys().collect::<Vec<_>>()
+    });
+
+    assert_eq!(
+        files,
+        [
+            "base/goblin-Enemy.grug",
+            "extra/goblin-Enemy.grug",
+            "extra/orc-Enemy.grug"
+        ]
+    );
+}
+
+#[test]
+fn game_functions_match_the_mod_api() {
+    let ok = isolated("game_functions_match_the_mod_api", || {
+        let (grug, _) = init_grug("game_functions");
+        grug.check_game_functions().is_ok()
+    });
+
+    assert!(ok);
+}
+
+#[test]
+fn scripts_read_tables_and_custom_parameters() {
+    let log = isolated("scripts_read_tables_and_custom_parameters", || {
+        let (grug, _) = init_grug("tables");
+        grug.publish_table(
+            "items",
+            [(
+                1,
+                TableRow::from([
+                    // C strings can't hold the NUL, so it gets dropped
+                    ("name".to_string(), "Sw\0ord".into()),
+                    ("price".to_string(), 12.into()),
+                ]),
+            )],
+        );
+
+        for item in [1, 2] {
+            let mut args = Arguments::new(vec![GrugValue::I32(item)]);
+            grug.activate_on_function("Player", "on_loot", &mut args)
+                .unwrap();
+        }
+        take_log()
+    });
+
+    assert_eq!(log, ["Sword", "12 gold"]);
+}
+
+#[test]
+fn struct_parameters_reject_ids_of_other_types() {
+    let (log, errors) = isolated("struct_parameters_reject_ids_of_other_types", || {
+        let (grug, _) = init_grug("struct_parameters");
+
+        let position = Position { x: 3, y: 4 };
+        let mut args = Arguments::new(vec![GrugValue::Custom(CustomValue::owned(position))]);
+        grug.activate_on_function("Player", "on_move", &mut args)
+            .unwrap();
+
+        let mut health = Health { value: 20 };
+        let mut args = Arguments::new(vec![GrugValue::custom(&mut health)]);
+        let _ = grug.activate_on_function("Player", "on_misplace", &mut args);
+
+        let errors: Vec<_> = grug
+            .snapshot()
+            .recent_errors
+            .iter()
+            .map(|x| (x.reason.clone(), x.on_function.clone()))
+            .collect();
+        (take_log(), errors)
+    });
+
+    assert_eq!(log, ["3,4"]);
+    assert_eq!(
+        errors,
+        [(
+            "`position` is not a `sample_game::Position`".to_string(),
+            "on_misplace".to_string()
+        )]
+    );
+}
+
+#[test]
+fn mod_updates_can_be_rolled_back() {
+    let (updated, rolled_back, broken, kept, unrecoverable) =
+        isolated("mod_updates_can_be_rolled_back", || {
+            let (grug, mods) = init_grug("update");
+            let tick = || {
+                grug.activate_on_function("Enemy", "on_tick", &mut Arguments::empty())
+                    .unwrap();
+                sorted(take_log())
+            };
+            tick();
+
+            let uruk = b"on_tick() {\n    log(\"uruk\")\n}\n".to_vec();
+            grug.apply_mod_update("extra", vec![("orc-Enemy.grug", uruk)], vec![])
+                .unwrap();
+            let updated = tick();
+
+            grug.rollback_mod("extra").unwrap();
+            let rolled_back = tick();
+
+            // Failing to compile undoes the update right away
+            let typo = b"on_tick() {\n    log(\n}\n".to_vec();
+            let broken = matches!(
+                grug.apply_mod_update("extra", vec![("orc-Enemy.grug", typo)], vec![]),
+                Err(GrugError::UpdateRolledBack { .. })
+            );
+            let kept = tick();
+
+            // Rolling back can't help when another mod doesn't compile either
+            rewrite(
+                &mods.join("base/goblin-Enemy.grug"),
+                "on_tick() {\n    log(\n}\n",
+            );
+            let uruk = b"on_tick() {\n    log(\"uruk\")\n}\n".to_vec();
+            let unrecoverable = matches!(
+                grug.apply_mod_update("extra", vec![("orc-Enemy.grug", uruk)], vec![]),
+                Err(GrugError::UpdateRollbackFailed { .. })
+            );
+
+            (updated, rolled_back, broken, kept, unrecoverable)
+        });
+
+    assert_eq!(updated, ["goblin", "uruk"]);
+    assert_eq!(rolled_back, ["goblin", "orc"]);
+    assert!(broken);
+    assert_eq!(kept, ["goblin", "orc"]);
+    assert!(unrecoverable);
+}
+
+#[cfg(feature = "cache")]
+#[test]
+fn the_cache_notices_changed_sources() {
+    let (fresh, stale) = isolated("the_cache_notices_changed_sources", || {
+        let (grug, mods) = init_grug("cache");
+        grug.compile_all_mods().unwrap();
+        let fresh = grug.verify_cache().len();
+
+        rewrite(
+            &mods.join("extra/orc-Enemy.grug"),
+            "on_tick() {\n    log(\"uruk\")\n}\n",
+        );
+        let stale: Vec<_> = grug
+            .verify_cache()
+            .into_iter()
+            .map(|x| {
+                (
+                    x.grug_path.ends_with("extra/orc-Enemy.grug"),
+                    x.reason == StaleReason::SourceChanged,
+                )
+            })
+            .collect();
+        (fresh, stale)
+    });
+
+    assert_eq!(fresh, 0);
+    assert_eq!(stale, [(true, true)]);
+}
+
+#[cfg(feature = "commands")]
+struct Spawn {
+    x: f32,
+    y: f32,
+}
+
+#[cfg(feature = "commands")]
+impl DeserializeFromArgs for Spawn {
+    fn from_args(kind: &str, [x, y, _]: [f32; 3]) -> Result<Self, String> {
+        match kind {
+            "spawn" => Ok(Self { x, y }),
+            _ => Err(format!("Unknown command `{kind}`")),
+        }
+    }
+}
+
+#[cfg(feature = "commands")]
+#[test]
+fn commands_reach_the_newest_buffer() {
+    let commands = isolated("commands_reach_the_newest_buffer", || {
+        let (grug, mods) = init_grug_with("commands", ModAPI::add_command_buffer);
+        rewrite(
+            &mods.join("extra/orc-Enemy.grug"),
+            "on_tick() {\n    emit_command(\"spawn\", 1.0, 2.0, 0.0)\n}\n",
+        );
+
+        // Like `buffer = CommandBuffer::new()`, where the old buffer gets dropped after the new one is made
+        let old = CommandBuffer::<Spawn>::new();
+        let commands = CommandBuffer::<Spawn>::new();
+        drop(old);
+
+        grug.activate_on_function("Enemy", "on_tick", &mut Arguments::empty())
+            .unwrap();
+        let spawned: Vec<_> = commands.drain().into_iter().map(|x| (x.x, x.y)).collect();
+        spawned
+    });
+
+    assert_eq!(commands, [(1.0, 2.0)]);
+}
+
+#[cfg(feature = "settings")]
+#[test]
+fn scripts_read_the_settings_of_their_own_mod() {
+    let (defaults, changed) = isolated("scripts_read_the_settings_of_their_own_mod", || {
+        let (grug, mods) = init_grug_with("settings", ModAPI::add_mod_settings);
+        for (mod_name, greeting) in [("base", "hi"), ("extra", "yo")] {
+            let about = format!(
+                "{{\"name\": \"{mod_name}\", \"version\": \"1.0.0\", \"game_version\": \"1.0.0\", \"author\": \"grug-rs\", \
+                \"settings\": {{\"greeting\": {{\"type\": \"string\", \"default\": \"{greeting}\"}}}}}}"
+            );
+            write(mods.join(mod_name).join("about.json"), about).unwrap();
+        }
+        let source = "on_tick() {\n    log(get_setting_string(\"greeting\"))\n}\n";
+        rewrite(&mods.join("base/goblin-Enemy.grug"), source);
+        rewrite(&mods.join("extra/orc-Enemy.grug"), source);
+        grug.load_mod_settings().unwrap();
+
+        grug.activate_on_function("Enemy", "on_tick", &mut Arguments::empty())
+            .unwrap();
+        let defaults = sorted(take_log());
+
+        grug.set_mod_setting("extra", "greeting", SettingValue::String("hey".to_string()))
+            .unwrap();
+        grug.activate_on_function("Enemy", "on_tick", &mut Arguments::empty())
+            .unwrap();
+        (defaults, sorted(take_log()))
+    });
+
+    assert_eq!(defaults, ["hi", "yo"]);
+    assert_eq!(changed, ["hey", "hi"]);
+}
+
+#[cfg(feature = "settings")]
+#[test]
+fn mod_settings_only_save_what_can_be_loaded_again() {
+    let (non_finite, kept, corrupt) = isolated(
+        "mod_settings_only_save_what_can_be_loaded_again",
+        || {
+            let (grug, mods) = init_grug_with("settings_saving", ModAPI::add_mod_settings);
+            let about = "{\"name\": \"base\", \"version\": \"1.0.0\", \"game_version\": \"1.0.0\", \"author\": \"grug-rs\", \
+                \"settings\": {\"speed\": {\"type\": \"f32\", \"default\": \"1.5\"}}}";
+            write(mods.join("base/about.json"), about).unwrap();
+
+            grug.set_mod_setting("base", "speed", SettingValue::F32(2.0))
+                .unwrap();
+            let non_finite = matches!(
+                grug.set_mod_setting("base", "speed", SettingValue::F32(f32::NAN)),
+                Err(GrugError::NonFiniteModSetting { .. })
+            );
+            let kept = grug.mod_settings("base").unwrap()[0].value.clone();
+
+            // A saved file that can't be read is an error, instead of quietly losing every setting
+            let saved = mods
+                .parent()
+                .unwrap()
+                .join("mods_dll/mod_settings/base.json");
+            write(&saved, "{\"speed\": null}").unwrap();
+            let corrupt = matches!(
+                grug.load_mod_settings(),
+                Err(GrugError::Deserialize { path, .. }) if path == saved
+            );
+
+            (non_finite, kept, corrupt)
+        },
+    );
+
+    assert!(non_finite);
+    assert_eq!(kept, SettingValue::F32(2.0));
+    assert!(corrupt);
+}
+
+#[test]
+fn failing_to_register_an_entity_type_changes_nothing() {
+    let (failed, unknown) = isolated("failing_to_register_an_entity_type_changes_nothing", || {
+        let folder = sample_game("register_entity");
+        let mod_api_path = folder.join("mod_api.json");
+        copy("./tests/sample_game/mod_api.json", &mod_api_path).unwrap();
+        let mut grug = Grug::new(
+            None,
+            &mod_api_path,
+            folder.join("mods"),
+            folder.join("mods_dll"),
+            1000,
+        )
+        .unwrap();
+
+        // Writing to a folder fails
+        std::fs::remove_file(&mod_api_path).unwrap();
+        create_dir_all(&mod_api_path).unwrap();
+
+        let turret = Entity {
+            description: "A turret placed in the editor".to_string(),
+            on_functions: LinkedHashMap::new(),
+        };
+        let failed = matches!(
+            grug.register_entity_type("Turret", turret),
+            Err(GrugError::WriteModAPI { .. })
+        );
+        let unknown = matches!(
+            grug.activate_on_function("Turret", "on_tick", &mut Arguments::empty()),
+            Err(GrugError::NotAnEntity { .. })
+        );
+        (failed, unknown)
+    });
+
+    assert!(failed);
+    assert!(unknown);
+}
@@ -0,0 +1,39 @@
+use std::{env::temp_dir, fs::create_dir_all};
+
+use grug_rs::{Grug, test_isolation::isolated};
+
+fn init_grug(name: &str) -> Grug {
+    let folder = temp_dir().join(name);
+    create_dir_all(&folder).unwrap();
+
+    Grug::new(
+        None,
+        "./examples/hello_world/mod_api.json",
+        folder.join("mods"),
+        folder.join("mods_dll"),
+        1000,
+    )
+    .unwrap()
+}
+
+// Both of these initialize grug, which would fail if they ran in the same process
+
+#[test]
+fn first_grug() {
+    let pid = isolated("first_grug", || {
+        init_grug("grug_rs_first_grug");
+        std::process::id()
+    });
+
+    assert_ne!(pid, std::process::id());
+}
+
+#[test]
+fn second_grug() {
+    let pid = isolated("second_grug", || {
+        init_grug("grug_rs_second_grug");
+        std::process::id()
+    });
+
+    assert_ne!(pid, std::process::id());
+}